@@ -1,30 +1,99 @@
 use crate::ChatClient;
+use crate::chat::recording::replay;
 use crate::chat::state::AppState;
-use crate::command::InputCommand;
-use crate::dice::Dice;
-use crate::events::{ChatEvent, NetworkEvent, SystemEvent};
+use crate::command::{COMMANDS, CommandSpec, InputCommand, TextPart, find_command};
+use crate::dice::{Dice, RollId, RollVisibility, Seed, Verification, commitment, derive_seed};
+use crate::events::{ChatEvent, InlineSegment, NetworkEvent, SystemEvent};
+use crate::initiative::Initiative;
+use crate::macros::{Macros, parse_expansion};
+use crate::message::InlinePart;
+use crate::snapshot::Snapshot;
+use crate::status::StatusChange;
+use crate::tables::{self, Table};
 use crate::ui::{ChatRenderer, InputEvent};
 use anyhow::Result;
 use arboard::Clipboard;
+use iroh::EndpointId;
+use std::collections::HashMap;
 use std::ops::ControlFlow;
-use tokio::sync::broadcast;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tui_input::InputRequest;
 use tui_input::backend::crossterm::EventHandler;
 
+/// How long a committed roll waits for the seeds of other peers before being revealed
+const ROLL_SEED_TIMEOUT: Duration = Duration::from_millis(1000);
+/// How often the DM shares the game state besides every change and peers joining
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+/// Maximum number of `[[...]]` rolls in a single chat message
+const MAX_INLINE_ROLLS: usize = 10;
+
+/// A roll we have committed to, collecting seeds from the other peers
+struct PendingRoll {
+    dice: Dice,
+    secret: Seed,
+    seeds: Vec<(EndpointId, Seed)>,
+    kind: RollKind,
+}
+
+/// What a pending roll is for, deciding how it is revealed
+enum RollKind {
+    Single(RollVisibility),
+    /// A part of an inline roll message, revealed together with the message
+    Inline(u64),
+    /// A roll on a random table, revealed with the picked entry
+    Table(Table),
+    /// A public roll whose result is our initiative
+    Initiative,
+}
+
+/// A chat message waiting for the seeds of its inline rolls
+enum PendingPart {
+    Text(String),
+    Roll(RollId),
+}
+
 pub struct ChatApp<UI: ChatRenderer> {
     renderer: UI,
     app_state: AppState,
     client: Option<ChatClient>,
     shutdown_tx: broadcast::Sender<()>,
+    // weak, so the event channel still closes once the backend is gone
+    event_tx: mpsc::WeakSender<SystemEvent>,
+    pending_rolls: HashMap<RollId, PendingRoll>,
+    pending_messages: HashMap<u64, Vec<PendingPart>>,
+    /// Our display name, changed by `/nick`
+    username: String,
+    macros: Macros,
+    /// The directory random tables are loaded from
+    tables: PathBuf,
+    /// The game state, only changed here when we are the DM
+    snapshot: Snapshot,
     _clipboard: Option<Clipboard>,
 }
 
 impl<UI: ChatRenderer> ChatApp<UI> {
-    pub fn new(renderer: UI, shutdown_tx: broadcast::Sender<()>) -> Self {
+    pub fn new(
+        renderer: UI,
+        shutdown_tx: broadcast::Sender<()>,
+        event_tx: mpsc::WeakSender<SystemEvent>,
+        username: String,
+        macros: Macros,
+        tables: PathBuf,
+    ) -> Self {
         Self {
             renderer,
             app_state: AppState::default(),
             client: None,
             shutdown_tx,
+            event_tx,
+            pending_rolls: HashMap::new(),
+            pending_messages: HashMap::new(),
+            username,
+            macros,
+            tables,
+            snapshot: Snapshot::default(),
             _clipboard: None,
         }
     }
@@ -34,6 +103,10 @@ impl<UI: ChatRenderer> ChatApp<UI> {
     }
 
     pub fn set_client(&mut self, client: ChatClient) {
+        self.app_state.set_roles(client.id(), client.dm());
+        if client.is_dm() {
+            self.spawn_snapshot_timer();
+        }
         self.client = Some(client);
     }
 
@@ -54,16 +127,37 @@ impl<UI: ChatRenderer> ChatApp<UI> {
         if !self.renderer.handle_ui_event(&input_event) {
             match input_event {
                 InputEvent::Submit => {
-                    let message = self.app_state.input_mut().value_and_reset();
+                    self.app_state.cancel_completion();
+                    let message = self.app_state.take_input();
                     if !message.is_empty() {
                         let command = InputCommand::from(message);
                         return self.handle_command(command).await;
                     }
                 }
+                InputEvent::Complete => {
+                    let macros = self.macros.iter().map(|(name, _)| name).collect();
+                    self.app_state.complete(macros, tables::names(&self.tables));
+                }
+                InputEvent::HistoryPrevious => {
+                    self.app_state.cancel_completion();
+                    self.app_state.history_previous();
+                }
+                InputEvent::HistoryNext => {
+                    self.app_state.cancel_completion();
+                    self.app_state.history_next();
+                }
+                InputEvent::Newline => {
+                    self.app_state.cancel_completion();
+                    self.app_state
+                        .input_mut()
+                        .handle(InputRequest::InsertChar('\n'));
+                }
                 InputEvent::Close => {
                     self.app_state.close_error_popup();
+                    self.app_state.cancel_completion();
                 }
                 InputEvent::Terminal(event) => {
+                    self.app_state.cancel_completion();
                     self.app_state.input_mut().handle_event(&event);
                 }
                 InputEvent::Redraw => {}
@@ -74,8 +168,35 @@ impl<UI: ChatRenderer> ChatApp<UI> {
         Ok(ControlFlow::Continue(()))
     }
 
+    fn help(name: Option<&str>) -> ChatEvent {
+        let commands: Vec<&CommandSpec> = match name {
+            None => COMMANDS.iter().collect(),
+            Some(name) => match find_command(name) {
+                Some(command) => vec![command],
+                None => return ChatEvent::Error(format!("Unknown command {}", name)),
+            },
+        };
+        ChatEvent::Help(
+            commands
+                .into_iter()
+                .map(|command| {
+                    let help = match command.aliases {
+                        [] => command.help.to_owned(),
+                        aliases => format!("{} (also {})", command.help, aliases.join(", ")),
+                    };
+                    (command.usage(), help)
+                })
+                .collect(),
+        )
+    }
+
     async fn handle_command(&mut self, command: InputCommand) -> Result<ControlFlow<()>> {
-        let event = if let Some(client) = &self.client {
+        let event = if let InputCommand::Help(name) = &command {
+            // usable while still connecting
+            Some(Self::help(name.as_deref()))
+        } else if let InputCommand::Invalid(error) = command {
+            Some(ChatEvent::Error(error))
+        } else if let Some(client) = &self.client {
             match command {
                 InputCommand::Quit => {
                     client.broadcast_left().await?;
@@ -86,36 +207,609 @@ impl<UI: ChatRenderer> ChatApp<UI> {
                 }
                 InputCommand::Broadcast(message) => {
                     client.broadcast_text(message.clone()).await?;
-                    ChatEvent::MessageSent(message)
+                    Some(ChatEvent::MessageSent(message))
                 }
+                InputCommand::InlineRolls(parts) => self.commit_inline_rolls(parts).await?,
                 InputCommand::ChangeName(name) => {
                     let msg = format!("Changed name to {}", &name);
-                    client.broadcast_join(name).await?;
-                    ChatEvent::SystemStatus(msg)
+                    client.broadcast_join(name.clone()).await?;
+                    self.username = name;
+                    Some(ChatEvent::SystemStatus(msg))
+                }
+                InputCommand::Emote(text) => {
+                    client.broadcast_emote(text.clone()).await?;
+                    Some(ChatEvent::Emote {
+                        author: self.username.clone(),
+                        text,
+                    })
+                }
+                InputCommand::Kick(name) => {
+                    if !client.is_dm() {
+                        Some(ChatEvent::Error("Only the DM can kick players.".to_owned()))
+                    } else {
+                        match self.app_state.peer_id(&name) {
+                            Ok(id) => {
+                                client.broadcast_kick(id).await?;
+                                Some(ChatEvent::PeerKicked { id, name })
+                            }
+                            Err(e) => Some(ChatEvent::Error(e.to_string())),
+                        }
+                    }
+                }
+                InputCommand::Whisper { to, text } => match self.app_state.peer_id(&to) {
+                    Ok(id) => {
+                        client.broadcast_whisper(id, text.clone()).await?;
+                        Some(ChatEvent::Whisper {
+                            peer: to,
+                            text,
+                            incoming: false,
+                        })
+                    }
+                    Err(e) => Some(ChatEvent::Error(e.to_string())),
+                },
+                InputCommand::Ooc(text) => {
+                    client.broadcast_ooc(text.clone()).await?;
+                    Some(ChatEvent::Ooc { author: None, text })
+                }
+                InputCommand::DiceRoll(dice_str) => {
+                    self.commit_roll(&dice_str, RollVisibility::Public).await?
+                }
+                InputCommand::GmRoll(dice_str) => {
+                    self.commit_roll(&dice_str, RollVisibility::Gm).await?
+                }
+                InputCommand::BlindRoll(dice_str) => {
+                    self.commit_roll(&dice_str, RollVisibility::Blind).await?
+                }
+                InputCommand::RollMacro(name) => self.roll_macro(&name).await?,
+                InputCommand::AddMacro { name, expansion } => {
+                    match self.macros.insert(&name, &expansion) {
+                        Ok(()) => Some(match self.macros.save() {
+                            Ok(()) => ChatEvent::SystemStatus(format!("Saved macro {}", name)),
+                            Err(e) => ChatEvent::Error(format!(
+                                "Macro {} is only kept for this session: {:#}",
+                                name, e
+                            )),
+                        }),
+                        Err(e) => Some(ChatEvent::Error(e.to_string())),
+                    }
+                }
+                InputCommand::RemoveMacro(name) => {
+                    if self.macros.remove(&name) {
+                        Some(match self.macros.save() {
+                            Ok(()) => ChatEvent::SystemStatus(format!("Removed macro {}", name)),
+                            Err(e) => ChatEvent::Error(format!(
+                                "Macro {} is only removed for this session: {:#}",
+                                name, e
+                            )),
+                        })
+                    } else {
+                        Some(ChatEvent::Error(format!("No macro named {}", name)))
+                    }
+                }
+                InputCommand::ShareMacro(name) => match self.macros.get(&name) {
+                    Some(expansion) => {
+                        let status = format!("Shared macro {}: {}", name, expansion);
+                        client.broadcast_macro(name, expansion.to_owned()).await?;
+                        Some(ChatEvent::SystemStatus(status))
+                    }
+                    None => Some(ChatEvent::Error(format!("No macro named {}", name))),
+                },
+                InputCommand::ListMacros => {
+                    let mut macros = self.macros.iter().peekable();
+                    if macros.peek().is_none() {
+                        Some(ChatEvent::SystemStatus(
+                            "No macros yet, add one with /macro add <name> <roll>".to_owned(),
+                        ))
+                    } else {
+                        for (name, expansion) in macros {
+                            self.app_state.apply_event(ChatEvent::SystemStatus(format!(
+                                "{}: {}",
+                                name, expansion
+                            )));
+                        }
+                        None
+                    }
+                }
+                InputCommand::Odds { expression, target } => {
+                    match expression
+                        .parse::<Dice>()
+                        .and_then(|dice| Ok((dice.odds()?, dice)))
+                    {
+                        Ok((odds, dice)) => Some(ChatEvent::Odds { dice, odds, target }),
+                        Err(e) => Some(ChatEvent::Error(e.to_string())),
+                    }
+                }
+                InputCommand::Table(name) => match Table::load(&self.tables, &name) {
+                    Ok(table) => {
+                        let roll_id = self
+                            .commit_dice(table.dice().clone(), RollKind::Table(table))
+                            .await?;
+                        self.spawn_reveal_timer(NetworkEvent::RevealRoll(roll_id));
+                        None
+                    }
+                    Err(e) => Some(ChatEvent::Error(format!("{:#}", e))),
+                },
+                InputCommand::Replay(path) => Some(match replay(&path) {
+                    Ok(replay) if replay.mismatches.is_empty() => ChatEvent::SystemStatus(format!(
+                        "Replayed {} rolls from {}, all came out the same",
+                        replay.rolls,
+                        path.display()
+                    )),
+                    Ok(replay) => {
+                        let mismatches: Vec<String> = replay
+                            .mismatches
+                            .iter()
+                            .map(|(recorded, roll)| {
+                                format!(
+                                    "{} by {} was {}, replayed {}",
+                                    recorded.dice,
+                                    recorded.roller.fmt_short(),
+                                    recorded.result,
+                                    roll.total
+                                )
+                            })
+                            .collect();
+                        ChatEvent::Error(format!(
+                            "{} of {} rolls from {} came out differently: {}",
+                            mismatches.len(),
+                            replay.rolls,
+                            path.display(),
+                            mismatches.join("; ")
+                        ))
+                    }
+                    Err(e) => ChatEvent::Error(format!("{:#}", e)),
+                }),
+                InputCommand::Stats => {
+                    self.app_state.toggle_stats();
+                    None
+                }
+                command @ (InputCommand::RollInitiative(_)
+                | InputCommand::SetInitiative(_)
+                | InputCommand::AddNpc { .. }
+                | InputCommand::RemoveInitiative(_)
+                | InputCommand::ClearInitiative
+                | InputCommand::NextTurn) => self.initiative_command(command).await?,
+                InputCommand::ChangeStatus(change) => {
+                    if client.is_dm() {
+                        self.change_status(None, change).await?
+                    } else if !change.name().eq_ignore_ascii_case(&self.username) {
+                        Some(ChatEvent::Error(
+                            "Only the DM can change other combatants.".to_owned(),
+                        ))
+                    } else {
+                        client.broadcast_status_change(change).await?;
+                        None
+                    }
+                }
+                InputCommand::ToggleStatus => {
+                    self.app_state.toggle_status();
+                    None
+                }
+                InputCommand::ToggleTimestamps => {
+                    self.app_state.toggle_timestamps();
+                    None
+                }
+                InputCommand::Help(_) | InputCommand::Invalid(_) => None,
+            }
+        } else {
+            Some(ChatEvent::Error("Wait for connection...".to_string()))
+        };
+
+        if let Some(event) = event {
+            self.app_state.apply_event(event);
+        }
+        self.renderer.draw(&self.app_state).await?;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Handles the initiative commands, the turn order being only changed by the DM
+    async fn initiative_command(&mut self, command: InputCommand) -> Result<Option<ChatEvent>> {
+        let Some(client) = &self.client else {
+            return Ok(None);
+        };
+        let is_dm = client.is_dm();
+        match command {
+            InputCommand::RollInitiative(roll) => match roll.parse::<Dice>() {
+                Ok(dice) => {
+                    let roll_id = self.commit_dice(dice, RollKind::Initiative).await?;
+                    self.spawn_reveal_timer(NetworkEvent::RevealRoll(roll_id));
+                    Ok(None)
+                }
+                Err(e) => Ok(Some(ChatEvent::Error(e.to_string()))),
+            },
+            InputCommand::SetInitiative(score) => {
+                self.set_own_initiative(score).await?;
+                Ok(Some(ChatEvent::SystemStatus(format!(
+                    "Your initiative is {}",
+                    score
+                ))))
+            }
+            InputCommand::NextTurn if is_dm => self.next_turn().await,
+            InputCommand::NextTurn => {
+                let own_turn = self
+                    .app_state
+                    .initiative()
+                    .and_then(Initiative::current)
+                    .is_some_and(|entry| entry.player == Some(client.id()));
+                if !own_turn {
+                    return Ok(Some(ChatEvent::Error(
+                        "Only the DM can pass the turn when it is not yours.".to_owned(),
+                    )));
+                }
+                client.broadcast_end_turn().await?;
+                Ok(None)
+            }
+            _ if !is_dm => Ok(Some(ChatEvent::Error(
+                "Only the DM can change the turn order.".to_owned(),
+            ))),
+            InputCommand::AddNpc { name, initiative } => {
+                let score = match initiative.parse::<i64>() {
+                    Ok(score) => score,
+                    Err(_) => match initiative.parse::<Dice>() {
+                        Ok(dice) => dice.roll_with(&mut rand::rng()).total,
+                        Err(e) => return Ok(Some(ChatEvent::Error(e.to_string()))),
+                    },
+                };
+                self.snapshot.initiative.set_npc(name.clone(), score);
+                self.share_snapshot(Vec::new()).await?;
+                Ok(Some(ChatEvent::SystemStatus(format!(
+                    "{}'s initiative is {}",
+                    name, score
+                ))))
+            }
+            InputCommand::RemoveInitiative(name) => {
+                if !self.snapshot.initiative.remove(&name) {
+                    return Ok(Some(ChatEvent::Error(format!(
+                        "Nobody called {} is in the turn order.",
+                        name
+                    ))));
+                }
+                self.share_snapshot(Vec::new()).await?;
+                Ok(None)
+            }
+            InputCommand::ClearInitiative => {
+                self.snapshot.initiative.clear();
+                self.share_snapshot(Vec::new()).await?;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Puts our initiative in the turn order, or sends it to the DM to do so
+    async fn set_own_initiative(&mut self, score: i64) -> Result<()> {
+        let Some(client) = &self.client else {
+            return Ok(());
+        };
+        if !client.is_dm() {
+            return client.broadcast_initiative(score).await;
+        }
+        self.snapshot
+            .initiative
+            .set_player(client.id(), self.username.clone(), score);
+        self.share_snapshot(Vec::new()).await
+    }
+
+    async fn next_turn(&mut self) -> Result<Option<ChatEvent>> {
+        let round = self.snapshot.initiative.round();
+        if let Err(e) = self.snapshot.initiative.next() {
+            return Ok(Some(ChatEvent::Error(e.to_string())));
+        }
+
+        // the conditions count down from the second round on
+        let mut ended = Vec::new();
+        if round > 0 && self.snapshot.initiative.round() > round {
+            ended = self.snapshot.status.next_round();
+        }
+        self.share_snapshot(ended).await?;
+        Ok(None)
+    }
+
+    /// Applies a change to a combatant, only when we are the DM
+    /// Applies a change made by us, or by the player who sent it
+    async fn change_status(
+        &mut self,
+        player: Option<EndpointId>,
+        change: StatusChange,
+    ) -> Result<Option<ChatEvent>> {
+        let result = match player {
+            Some(player) => self.snapshot.status.apply_for(player, change),
+            None => self.snapshot.status.apply(change),
+        };
+        match result {
+            Ok(note) => {
+                self.share_snapshot(note.into_iter().collect()).await?;
+                Ok(None)
+            }
+            Err(e) => Ok(Some(ChatEvent::Error(e.to_string()))),
+        }
+    }
+
+    /// Sends the changed game state to the other peers and shows it
+    async fn share_snapshot(&mut self, notes: Vec<String>) -> Result<()> {
+        self.snapshot.bump();
+        if let Some(client) = &self.client {
+            client
+                .broadcast_snapshot(self.snapshot.clone(), notes.clone())
+                .await?;
+        }
+        self.app_state.apply_event(ChatEvent::SnapshotReceived {
+            snapshot: self.snapshot.clone(),
+            notes,
+        });
+        Ok(())
+    }
+
+    /// Commits to a roll, which is shown once revealed after the other peers sent their seeds
+    async fn commit_roll(
+        &mut self,
+        dice_str: &str,
+        visibility: RollVisibility,
+    ) -> Result<Option<ChatEvent>> {
+        match dice_str.parse::<Dice>() {
+            Ok(dice) => {
+                let roll_id = self.commit_dice(dice, RollKind::Single(visibility)).await?;
+                self.spawn_reveal_timer(NetworkEvent::RevealRoll(roll_id));
+                Ok(None)
+            }
+            Err(e) => Ok(Some(ChatEvent::Error(e.to_string()))),
+        }
+    }
+
+    async fn commit_dice(&mut self, dice: Dice, kind: RollKind) -> Result<RollId> {
+        let roll_id = rand::random();
+        let Some(client) = &self.client else {
+            return Ok(roll_id);
+        };
+        let secret = rand::random();
+        client
+            .broadcast_roll_commit(roll_id, commitment(roll_id, &secret, &dice)?)
+            .await?;
+        self.pending_rolls.insert(
+            roll_id,
+            PendingRoll {
+                dice,
+                secret,
+                seeds: Vec::new(),
+                kind,
+            },
+        );
+        Ok(roll_id)
+    }
+
+    /// Shares the game state periodically, in case a peer missed a change
+    fn spawn_snapshot_timer(&self) {
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SNAPSHOT_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(event_tx) = event_tx.upgrade() else {
+                    break;
+                };
+                if event_tx
+                    .send(SystemEvent::Network(NetworkEvent::ShareSnapshot))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Sends the event once the other peers had the time to contribute their seeds
+    fn spawn_reveal_timer(&self, event: NetworkEvent) {
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(ROLL_SEED_TIMEOUT).await;
+            if let Some(event_tx) = event_tx.upgrade() {
+                _ = event_tx.send(SystemEvent::Network(event)).await;
+            }
+        });
+    }
+
+    /// Commits to every `[[...]]` roll of a chat message, which is sent once they are revealed
+    async fn commit_inline_rolls(&mut self, parts: Vec<TextPart>) -> Result<Option<ChatEvent>> {
+        let rolls = parts
+            .iter()
+            .filter(|part| matches!(part, TextPart::Roll(_)))
+            .count();
+        if rolls > MAX_INLINE_ROLLS {
+            return Ok(Some(ChatEvent::Error(format!(
+                "Too many inline rolls! Please use {} or fewer in a message.",
+                MAX_INLINE_ROLLS
+            ))));
+        }
+
+        // parses all rolls up front, so nothing is committed when any of them is invalid
+        let mut rolls = Vec::with_capacity(rolls);
+        for part in &parts {
+            if let TextPart::Roll(expression) = part {
+                match expression.parse::<Dice>() {
+                    Ok(dice) => rolls.push(dice),
+                    Err(e) => return Ok(Some(ChatEvent::Error(e.to_string()))),
                 }
-                InputCommand::DiceRoll(dice_str) => match dice_str.parse::<Dice>() {
-                    Ok(dice) => {
-                        let (result, rolls) = dice.roll();
+            }
+        }
+
+        let message_id = rand::random();
+        let mut rolls = rolls.into_iter();
+        let mut pending = Vec::with_capacity(parts.len());
+        for part in parts {
+            match part {
+                TextPart::Text(text) => pending.push(PendingPart::Text(text)),
+                TextPart::Roll(_) => {
+                    if let Some(dice) = rolls.next() {
+                        let roll_id = self.commit_dice(dice, RollKind::Inline(message_id)).await?;
+                        pending.push(PendingPart::Roll(roll_id));
+                    }
+                }
+            }
+        }
+        self.pending_messages.insert(message_id, pending);
+        self.spawn_reveal_timer(NetworkEvent::RevealMessage(message_id));
+        Ok(None)
+    }
+
+    /// Rolls every roll of a macro, or the argument itself when there is no such macro
+    async fn roll_macro(&mut self, name: &str) -> Result<Option<ChatEvent>> {
+        let expansion = self.macros.get(name).unwrap_or(name).to_owned();
+        let rolls = match parse_expansion(&expansion) {
+            Ok(rolls) => rolls,
+            Err(e) => return Ok(Some(ChatEvent::Error(e.to_string()))),
+        };
+        for dice in rolls {
+            let roll_id = self
+                .commit_dice(dice, RollKind::Single(RollVisibility::Public))
+                .await?;
+            self.spawn_reveal_timer(NetworkEvent::RevealRoll(roll_id));
+        }
+        Ok(None)
+    }
+
+    /// Rolls the pending dice from the collected seeds and reveals the roll to the other peers
+    async fn reveal_roll(&mut self, roll_id: RollId) -> Result<()> {
+        // inline rolls are only revealed together with their message
+        if let Some(RollKind::Inline(_)) = self.pending_rolls.get(&roll_id).map(|p| &p.kind) {
+            return Ok(());
+        }
+        let (Some(client), Some(pending)) = (&self.client, self.pending_rolls.remove(&roll_id))
+        else {
+            return Ok(());
+        };
+
+        let seed = derive_seed(&client.id(), roll_id, &pending.secret, &pending.seeds);
+        let roll = pending.dice.roll_seeded(seed);
+        let initiative = matches!(pending.kind, RollKind::Initiative);
+        let visibility = match pending.kind {
+            RollKind::Single(visibility) => visibility,
+            RollKind::Initiative => RollVisibility::Public,
+            RollKind::Inline(_) => return Ok(()),
+            RollKind::Table(table) => {
+                let event = match table.resolve(&self.tables, &seed, roll.total, roll.terms) {
+                    Ok((entry, steps)) => {
                         client
-                            .broadcast_dice_roll(result, dice, rolls.clone())
+                            .broadcast_table_roll(
+                                roll_id,
+                                entry.clone(),
+                                steps.clone(),
+                                pending.secret,
+                                pending.seeds,
+                            )
                             .await?;
-                        ChatEvent::DiceRolled {
-                            result,
-                            rolls,
-                            dice,
+                        ChatEvent::TableRolled {
+                            roller: client.id(),
                             author: None,
+                            entry,
+                            steps,
+                            verification: Verification::Verified,
                         }
                     }
                     Err(e) => ChatEvent::Error(e.to_string()),
-                },
+                };
+                self.app_state.apply_event(event);
+                return self.renderer.draw(&self.app_state).await;
             }
-        } else {
-            ChatEvent::Error("Wait for connection...".to_string())
         };
+        client
+            .broadcast_dice_roll(
+                roll_id,
+                pending.dice.clone(),
+                roll.clone(),
+                pending.secret,
+                pending.seeds,
+                visibility,
+            )
+            .await?;
+        let total = roll.total;
 
+        // the DM is the one the blind roll is hidden for, so they may see their own
+        let event = if visibility == RollVisibility::Blind && !client.is_dm() {
+            ChatEvent::HiddenRoll {
+                author: None,
+                dice: Some(pending.dice),
+                visibility,
+            }
+        } else {
+            ChatEvent::DiceRolled {
+                roller: client.id(),
+                result: roll.total,
+                terms: roll.terms,
+                dice: pending.dice,
+                author: None,
+                verification: Verification::Verified,
+                visibility,
+            }
+        };
         self.app_state.apply_event(event);
-        self.renderer.draw(&self.app_state).await?;
-        Ok(ControlFlow::Continue(()))
+        if initiative {
+            self.set_own_initiative(total).await?;
+        }
+        self.renderer.draw(&self.app_state).await
+    }
+
+    /// Whether all inline rolls of a message have collected the expected number of seeds
+    fn message_seeded(&self, message_id: u64, expected: usize) -> bool {
+        let Some(parts) = self.pending_messages.get(&message_id) else {
+            return false;
+        };
+        parts.iter().all(|part| match part {
+            PendingPart::Text(_) => true,
+            PendingPart::Roll(roll_id) => self
+                .pending_rolls
+                .get(roll_id)
+                .is_some_and(|roll| roll.seeds.len() >= expected),
+        })
+    }
+
+    /// Rolls all inline rolls of a message and sends the message together with the rolls
+    async fn reveal_message(&mut self, message_id: u64) -> Result<()> {
+        let (Some(client), Some(pending)) =
+            (&self.client, self.pending_messages.remove(&message_id))
+        else {
+            return Ok(());
+        };
+
+        let mut parts = Vec::with_capacity(pending.len());
+        let mut segments = Vec::with_capacity(pending.len());
+        for part in pending {
+            match part {
+                PendingPart::Text(text) => {
+                    parts.push(InlinePart::Text(text.clone()));
+                    segments.push(InlineSegment::Text(text));
+                }
+                PendingPart::Roll(roll_id) => {
+                    let Some(roll) = self.pending_rolls.remove(&roll_id) else {
+                        continue;
+                    };
+                    let seed = derive_seed(&client.id(), roll_id, &roll.secret, &roll.seeds);
+                    let result = roll.dice.roll_seeded(seed);
+                    segments.push(InlineSegment::Roll {
+                        result: result.total,
+                        dice: roll.dice.clone(),
+                        terms: result.terms.clone(),
+                        verification: Verification::Verified,
+                    });
+                    parts.push(InlinePart::Roll {
+                        roll_id,
+                        result: result.total,
+                        dice: roll.dice,
+                        terms: result.terms,
+                        secret: roll.secret,
+                        seeds: roll.seeds,
+                    });
+                }
+            }
+        }
+        client.broadcast_inline_rolls(parts).await?;
+
+        self.app_state.apply_event(ChatEvent::InlineRolled {
+            roller: client.id(),
+            author: None,
+            parts: segments,
+        });
+        self.renderer.draw(&self.app_state).await
     }
 
     pub async fn handle_system_event(&mut self, event: SystemEvent) -> Result<()> {
@@ -134,6 +828,85 @@ impl<UI: ChatRenderer> ChatApp<UI> {
                         self.renderer.draw(&self.app_state).await?;
                     }
                 }
+                NetworkEvent::ContributeSeed {
+                    roller,
+                    roll_id,
+                    seed,
+                } => {
+                    if let Some(client) = &self.client {
+                        client.broadcast_roll_seed(roller, roll_id, seed).await?;
+                    }
+                }
+                NetworkEvent::SeedReceived {
+                    from,
+                    roll_id,
+                    seed,
+                    expected,
+                } => {
+                    if let Some(pending) = self.pending_rolls.get_mut(&roll_id)
+                        && !pending
+                            .seeds
+                            .iter()
+                            .any(|(contributor, _)| *contributor == from)
+                    {
+                        pending.seeds.push((from, seed));
+                        let seeded = pending.seeds.len() >= expected;
+                        let message = match pending.kind {
+                            RollKind::Inline(message_id) => Some(message_id),
+                            _ => None,
+                        };
+                        match message {
+                            None if seeded => self.reveal_roll(roll_id).await?,
+                            Some(message_id) if self.message_seeded(message_id, expected) => {
+                                self.reveal_message(message_id).await?
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                NetworkEvent::RevealRoll(roll_id) => self.reveal_roll(roll_id).await?,
+                NetworkEvent::InitiativeReceived { from, name, score } => {
+                    self.snapshot.initiative.set_player(from, name, score);
+                    self.share_snapshot(Vec::new()).await?;
+                    self.renderer.draw(&self.app_state).await?
+                }
+                NetworkEvent::StatusChangeReceived { from, name, change } => {
+                    if let Some(ChatEvent::Error(e)) =
+                        self.change_status(Some(from), change).await?
+                    {
+                        self.app_state.apply_event(ChatEvent::SystemStatus(format!(
+                            "Could not apply {}'s change: {}",
+                            name, e
+                        )));
+                    }
+                    self.renderer.draw(&self.app_state).await?
+                }
+                NetworkEvent::TurnEnded(from) => {
+                    let own_turn = self
+                        .snapshot
+                        .initiative
+                        .current()
+                        .is_some_and(|entry| entry.player == Some(from));
+                    if own_turn && let Some(event) = self.next_turn().await? {
+                        self.app_state.apply_event(event);
+                    }
+                    self.renderer.draw(&self.app_state).await?
+                }
+                NetworkEvent::RevealMessage(message_id) => self.reveal_message(message_id).await?,
+                NetworkEvent::HistoryRequested(to) => {
+                    if let Some(client) = &self.client {
+                        client.broadcast_history(to).await?;
+                    }
+                }
+                NetworkEvent::ShareSnapshot => {
+                    if let Some(client) = &self.client
+                        && client.is_dm()
+                    {
+                        client
+                            .broadcast_snapshot(self.snapshot.clone(), Vec::new())
+                            .await?;
+                    }
+                }
             },
         }
         Ok(())
@@ -1,15 +1,37 @@
-use crate::events::{ChatEvent, NetworkEvent, SystemEvent};
-use crate::message::{Message, MessageBody};
-use anyhow::Result;
+use crate::chat::{Backlog, BacklogEntry, LamportClock, Recorder, SeenMessages};
+use crate::dice::{Commitment, Reveal, RollId, RollVisibility, Verification, derive_seed};
+use crate::events::{ChatEvent, InlineSegment, NetworkEvent, SystemEvent};
+use crate::message::{
+    DmAction, InlinePart, Message, MessageBody, MessageHeader, PlayerAction, now_millis,
+};
+use crate::tables::nested_rolls_match;
+use anyhow::{Result, anyhow};
 use futures_lite::StreamExt;
 use iroh::{Endpoint, EndpointId, protocol::Router};
+use iroh_gossip::TopicId;
 use iroh_gossip::api::{Event, GossipReceiver};
 use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::time::Duration;
 use tokio::sync::{broadcast, mpsc::Sender};
 
+/// How long a committed roll may stay unrevealed before the table is warned about it
+const REVEAL_TIMEOUT: Duration = Duration::from_secs(10);
+/// How far the time of a signed action may be from ours, older ones being replays
+const MAX_SIGNED_AGE: Duration = Duration::from_secs(120);
+
 #[derive(Default)]
 struct ChatState {
     users: HashMap<EndpointId, String>,
+    commitments: HashMap<(EndpointId, RollId), Commitment>,
+    /// Peers kicked by the DM, whose messages are ignored
+    kicked: HashSet<EndpointId>,
+    /// The version of the last game state applied
+    snapshot_version: Option<u64>,
+    /// IDs of the messages already handled, as gossip may deliver a message more than once
+    seen: SeenMessages,
+    /// The signers and times of the recent signed actions, to reject them when replayed
+    signed: HashSet<(EndpointId, u64)>,
 }
 
 impl ChatState {
@@ -24,13 +46,26 @@ impl ChatState {
             .unwrap_or("Unknown")
     }
 
+    /// Whether the signed action is recent and new, remembering it
+    fn accept_signed(&mut self, signer: EndpointId, timestamp: u64) -> bool {
+        let max_age = MAX_SIGNED_AGE.as_millis() as u64;
+        let now = now_millis();
+        if timestamp.abs_diff(now) > max_age {
+            return false;
+        }
+        self.signed
+            .retain(|(_, signed)| signed.abs_diff(now) <= max_age);
+        self.signed.insert((signer, timestamp))
+    }
+
     fn remove_user(&mut self, author: &EndpointId) -> Option<String> {
+        self.commitments.retain(|(roller, _), _| roller != author);
         self.users.remove(author)
     }
 }
 
 pub struct ChatBackend {
-    _endpoint: Endpoint,
+    endpoint: Endpoint,
     key: [u8; 32],
     state: ChatState,
     router: Router,
@@ -38,19 +73,26 @@ pub struct ChatBackend {
     event_tx: Sender<SystemEvent>,
     username: String,
     bad_actors: HashSet<EndpointId>,
+    dm: EndpointId,
+    recorder: Option<Recorder>,
+    backlog: Backlog,
+    clock: LamportClock,
+    /// The room the signed actions must be bound to, none accepted until it is set
+    topic: Option<TopicId>,
 }
 
 impl ChatBackend {
     pub fn new(
-        _endpoint: Endpoint,
+        endpoint: Endpoint,
         key: [u8; 32],
         router: Router,
         receiver: GossipReceiver,
         event_tx: Sender<SystemEvent>,
         username: String,
+        dm: EndpointId,
     ) -> Self {
         Self {
-            _endpoint,
+            endpoint,
             key,
             state: ChatState::default(),
             router,
@@ -58,21 +100,54 @@ impl ChatBackend {
             event_tx,
             username,
             bad_actors: HashSet::new(),
+            dm,
+            recorder: None,
+            backlog: Backlog::default(),
+            clock: LamportClock::default(),
+            topic: None,
         }
     }
 
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Shares the chat messages kept for peers joining late with the client
+    pub fn set_backlog(&mut self, backlog: Backlog) {
+        self.backlog = backlog;
+    }
+
+    /// Shares the Lamport clock with the client, moving it past every message received
+    pub fn set_clock(&mut self, clock: LamportClock) {
+        self.clock = clock;
+    }
+
+    /// Sets the room topic the signed actions are checked against
+    pub fn set_topic(&mut self, topic: TopicId) {
+        self.topic = Some(topic);
+    }
+
     pub async fn subscribe_loop(mut self, mut shutdown_rx: broadcast::Receiver<()>) {
+        let mut reveal_check = tokio::time::interval(REVEAL_TIMEOUT / 2);
         loop {
             tokio::select! {
                 _ = shutdown_rx.recv() => {
                     break;
                 }
 
+                _ = reveal_check.tick() => {
+                    if self.expire_commitments().await.is_err() {
+                        break;
+                    }
+                }
+
                 event_option = self.receiver.try_next() => {
                     match event_option {
-                        Ok(Some(event)) => {
-                            if let Err(e) = self.handle_event(event).await
-                                && self
+                        Ok(Some(event)) => match self.handle_event(event).await {
+                            Ok(ControlFlow::Break(())) => break,
+                            Ok(ControlFlow::Continue(())) => {}
+                            Err(e) => {
+                                if self
                                     .event_tx
                                     .send(SystemEvent::Ui(ChatEvent::Error(format!(
                                         "Failed to process event: {:?}",
@@ -80,10 +155,11 @@ impl ChatBackend {
                                     ))))
                                     .await
                                     .is_err()
-                            {
-                                break;
-                            };
-                        }
+                                {
+                                    break;
+                                }
+                            }
+                        },
                         _ => break,
                     }
                 }
@@ -108,10 +184,34 @@ impl ChatBackend {
         }
     }
 
-    async fn handle_event(&mut self, event: Event) -> Result<()> {
+    /// Warns about the rolls committed but never revealed, which a roller could abandon when
+    /// the result was not to their liking
+    async fn expire_commitments(&mut self) -> Result<()> {
+        let mut expired = Vec::new();
+        self.state.commitments.retain(|(roller, _), commitment| {
+            let pending = commitment.received.elapsed() < REVEAL_TIMEOUT;
+            if !pending {
+                expired.push(*roller);
+            }
+            pending
+        });
+        for roller in expired {
+            let name = self.display_name(roller);
+            self.event_tx
+                .send(SystemEvent::Ui(ChatEvent::SystemStatus(format!(
+                    "⚠ {} committed to a roll but never revealed it",
+                    name
+                ))))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Handles a gossip event, breaking once the DM kicked us from the table
+    async fn handle_event(&mut self, event: Event) -> Result<ControlFlow<()>> {
         if let Event::Received(msg) = event {
-            match Message::from_bytes(&msg.content)?.decrypt(&self.key) {
-                Ok(decrypted) => {
+            match Message::from_bytes(&msg.content)?.open_envelope(&self.key) {
+                Ok((header, decrypted)) => {
                     if self.bad_actors.remove(&msg.delivered_from) {
                         self.event_tx
                             .send(SystemEvent::Ui(ChatEvent::SystemStatus(format!(
@@ -120,7 +220,26 @@ impl ChatBackend {
                             ))))
                             .await?;
                     }
-                    self.handle_message(decrypted).await?;
+                    if !self.state.seen.insert(header.id) {
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                    if !self.clock.observe(&header) {
+                        return Err(anyhow!(
+                            "Ignored a message from {} with an implausible clock",
+                            self.display_name(decrypted.author())
+                        ));
+                    }
+                    if decrypted.is_chat() {
+                        self.backlog
+                            .insert(BacklogEntry::new(header, msg.content.to_vec()));
+                    }
+                    return self.handle_message(header, decrypted).await;
+                }
+                Err(e) if e.is::<postcard::Error>() => {
+                    return Err(anyhow!(
+                        "Ignored a malformed message delivered by {}",
+                        msg.delivered_from.fmt_short()
+                    ));
                 }
                 Err(e) => {
                     if self.bad_actors.insert(msg.delivered_from) {
@@ -135,68 +254,595 @@ impl ChatBackend {
                 }
             };
         }
-        Ok(())
+        Ok(ControlFlow::Continue(()))
     }
 
-    async fn handle_message(&mut self, body: MessageBody) -> Result<()> {
+    async fn handle_message(
+        &mut self,
+        header: MessageHeader,
+        body: MessageBody,
+    ) -> Result<ControlFlow<()>> {
+        if self.state.kicked.contains(&body.author()) {
+            return Ok(ControlFlow::Continue(()));
+        }
         match body {
             MessageBody::Joined { from, name } => {
                 match self.state.update_user(from, name.clone()) {
                     None => {
-                        self.event_tx
-                            .send(SystemEvent::Ui(ChatEvent::PeerJoined(name)))
+                        self.show(header, ChatEvent::PeerJoined { id: from, name })
                             .await?;
                         self.event_tx
                             .send(SystemEvent::Network(NetworkEvent::BroadcastJoin(
                                 self.username.clone(),
                             )))
                             .await?;
+                        if self.endpoint.id() == self.dm {
+                            self.event_tx
+                                .send(SystemEvent::Network(NetworkEvent::ShareSnapshot))
+                                .await?;
+                        }
                     }
                     Some(old_name) => {
                         if old_name != name {
-                            self.event_tx
-                                .send(SystemEvent::Ui(ChatEvent::PeerNameChange {
+                            self.show(
+                                header,
+                                ChatEvent::PeerNameChange {
+                                    id: from,
                                     old: old_name,
                                     new: name,
-                                }))
-                                .await?;
+                                },
+                            )
+                            .await?;
                         }
                     }
                 }
             }
             MessageBody::Text { from, text } => {
-                let name = self.state.resolve_name(from);
-                self.event_tx
-                    .send(SystemEvent::Ui(ChatEvent::MessageReceived {
+                let name = self.display_name(from);
+                self.show(
+                    header,
+                    ChatEvent::MessageReceived {
                         author: name.to_string(),
                         content: text,
+                    },
+                )
+                .await?;
+            }
+            MessageBody::Emote { from, text } => {
+                let name = self.display_name(from);
+                self.show(
+                    header,
+                    ChatEvent::Emote {
+                        author: name.to_string(),
+                        text,
+                    },
+                )
+                .await?;
+            }
+            MessageBody::Ooc { from, text } => {
+                let name = self.display_name(from);
+                self.show(
+                    header,
+                    ChatEvent::Ooc {
+                        author: Some(name.to_string()),
+                        text,
+                    },
+                )
+                .await?;
+            }
+            MessageBody::Whisper { from, to, message } => {
+                // everyone receives the whisper, but only the recipient can read it
+                if to != self.endpoint.id() {
+                    return Ok(ControlFlow::Continue(()));
+                }
+                match message.open_whisper(self.endpoint.secret_key(), &from)? {
+                    MessageBody::Text { from: sender, text } if sender == from => {
+                        let name = self.display_name(from);
+                        self.show(
+                            header,
+                            ChatEvent::Whisper {
+                                peer: name.to_string(),
+                                text,
+                                incoming: true,
+                            },
+                        )
+                        .await?;
+                    }
+                    _ => return Err(anyhow!("Received a malformed whisper")),
+                }
+            }
+            MessageBody::Left { from } => {
+                if let Some(name) = self.state.remove_user(&from) {
+                    self.show(header, ChatEvent::PeerLeft { id: from, name })
+                        .await?;
+                }
+            }
+            MessageBody::RollCommit {
+                from,
+                roll_id,
+                commitment,
+            } => {
+                let own_seed = rand::random();
+                self.state
+                    .commitments
+                    .insert((from, roll_id), Commitment::new(commitment, own_seed));
+                self.event_tx
+                    .send(SystemEvent::Network(NetworkEvent::ContributeSeed {
+                        roller: from,
+                        roll_id,
+                        seed: own_seed,
                     }))
                     .await?;
             }
-            MessageBody::Left { from } => {
-                if let Some(user) = self.state.remove_user(&from) {
+            MessageBody::RollSeed {
+                from,
+                roller,
+                roll_id,
+                seed,
+            } => {
+                if roller == self.endpoint.id() {
                     self.event_tx
-                        .send(SystemEvent::Ui(ChatEvent::PeerLeft(user)))
+                        .send(SystemEvent::Network(NetworkEvent::SeedReceived {
+                            from,
+                            roll_id,
+                            seed,
+                            expected: self.state.users.len(),
+                        }))
                         .await?;
+                } else if let Some(commitment) = self.state.commitments.get_mut(&(roller, roll_id))
+                {
+                    // everyone checks that the roller kept the seeds of the others
+                    commitment.add_seed(from, seed);
                 }
             }
             MessageBody::DiceRoll {
                 from,
+                roll_id,
                 result,
                 dice,
-                rolls,
+                terms,
+                secret,
+                seeds,
             } => {
-                let name = self.state.resolve_name(from);
-                self.event_tx
-                    .send(SystemEvent::Ui(ChatEvent::DiceRolled {
+                let reveal = Reveal {
+                    roller: &from,
+                    roll_id,
+                    dice: &dice,
+                    result,
+                    terms: &terms,
+                    secret: &secret,
+                    seeds: &seeds,
+                };
+                self.handle_dice_roll(header, reveal, RollVisibility::Public)
+                    .await?;
+            }
+            MessageBody::HiddenRoll {
+                from,
+                roll_id,
+                visibility,
+                sealed,
+            } => {
+                if self.endpoint.id() != self.dm {
+                    self.state.commitments.remove(&(from, roll_id));
+                    let name = self.display_name(from);
+                    self.show(
+                        header,
+                        ChatEvent::HiddenRoll {
+                            author: Some(name.to_string()),
+                            dice: None,
+                            visibility,
+                        },
+                    )
+                    .await?;
+                    return Ok(ControlFlow::Continue(()));
+                }
+
+                match sealed.open(self.endpoint.secret_key())? {
+                    MessageBody::DiceRoll {
+                        from: roller,
+                        roll_id: sealed_roll_id,
                         result,
-                        rolls,
                         dice,
+                        terms,
+                        secret,
+                        seeds,
+                    } if roller == from && sealed_roll_id == roll_id => {
+                        let reveal = Reveal {
+                            roller: &from,
+                            roll_id,
+                            dice: &dice,
+                            result,
+                            terms: &terms,
+                            secret: &secret,
+                            seeds: &seeds,
+                        };
+                        self.handle_dice_roll(header, reveal, visibility).await?;
+                    }
+                    _ => return Err(anyhow!("Received a malformed hidden roll")),
+                }
+            }
+            MessageBody::InlineRolls { from, parts } => {
+                let parts = parts
+                    .into_iter()
+                    .map(|part| match part {
+                        InlinePart::Text(text) => Ok(InlineSegment::Text(text)),
+                        InlinePart::Roll {
+                            roll_id,
+                            result,
+                            dice,
+                            terms,
+                            secret,
+                            seeds,
+                        } => {
+                            let commitment = self.state.commitments.remove(&(from, roll_id));
+                            let reveal = Reveal {
+                                roller: &from,
+                                roll_id,
+                                dice: &dice,
+                                result,
+                                terms: &terms,
+                                secret: &secret,
+                                seeds: &seeds,
+                            };
+                            self.record(&reveal)?;
+                            let verification =
+                                reveal.verify(&self.endpoint.id(), commitment.as_ref());
+                            Ok(InlineSegment::Roll {
+                                result,
+                                dice,
+                                terms,
+                                verification,
+                            })
+                        }
+                    })
+                    .collect::<Result<_>>()?;
+
+                let name = self.display_name(from);
+                self.show(
+                    header,
+                    ChatEvent::InlineRolled {
+                        roller: from,
                         author: Some(name.to_string()),
-                    }))
+                        parts,
+                    },
+                )
+                .await?;
+            }
+            MessageBody::TableRoll {
+                from,
+                roll_id,
+                entry,
+                steps,
+                secret,
+                seeds,
+            } => {
+                let Some(first) = steps.first() else {
+                    return Err(anyhow!("Received a malformed table roll"));
+                };
+                let commitment = self.state.commitments.remove(&(from, roll_id));
+                let reveal = Reveal {
+                    roller: &from,
+                    roll_id,
+                    dice: &first.dice,
+                    result: first.result,
+                    terms: &first.terms,
+                    secret: &secret,
+                    seeds: &seeds,
+                };
+                self.record(&reveal)?;
+                let verification = match reveal.verify(&self.endpoint.id(), commitment.as_ref()) {
+                    Verification::Verified => {
+                        let seed = derive_seed(&from, roll_id, &secret, &seeds);
+                        if nested_rolls_match(&seed, &steps) {
+                            Verification::Verified
+                        } else {
+                            Verification::Forged(
+                                "the nested table rolls do not match the seeds".to_owned(),
+                            )
+                        }
+                    }
+                    verification => verification,
+                };
+
+                let name = self.display_name(from);
+                self.show(
+                    header,
+                    ChatEvent::TableRolled {
+                        roller: from,
+                        author: Some(name.to_string()),
+                        entry,
+                        steps,
+                        verification,
+                    },
+                )
+                .await?;
+            }
+            MessageBody::MacroShared {
+                from,
+                name,
+                expansion,
+            } => {
+                let author = self.display_name(from);
+                self.show(
+                    header,
+                    ChatEvent::SystemStatus(format!("{}'s macro {}: {}", author, name, expansion)),
+                )
+                .await?;
+            }
+            MessageBody::HistoryRequest { from } => {
+                // only the DM answers, rather than every peer sending the whole backlog
+                if self.endpoint.id() == self.dm {
+                    self.event_tx
+                        .send(SystemEvent::Network(NetworkEvent::HistoryRequested(from)))
+                        .await?;
+                }
+            }
+            MessageBody::Player {
+                from,
+                action,
+                timestamp,
+                signature,
+            } => {
+                if self.endpoint.id() != self.dm {
+                    return Ok(ControlFlow::Continue(()));
+                }
+                let signed = self
+                    .topic
+                    .is_some_and(|topic| action.verify(&from, &topic, timestamp, &signature));
+                if !signed {
+                    return Err(anyhow!(
+                        "Ignored a player action not signed by {}",
+                        self.display_name(from)
+                    ));
+                }
+                if !self.state.accept_signed(from, timestamp) {
+                    return Err(anyhow!(
+                        "Ignored a stale or replayed action of {}",
+                        self.display_name(from)
+                    ));
+                }
+                self.handle_player_action(from, action).await?;
+            }
+            MessageBody::Dm {
+                from,
+                action,
+                timestamp,
+                signature,
+            } => {
+                let signed = self
+                    .topic
+                    .is_some_and(|topic| action.verify(&self.dm, &topic, timestamp, &signature));
+                if from != self.dm || !signed {
+                    return Err(anyhow!(
+                        "Ignored a DM action not signed by the DM, claimed by {}",
+                        self.display_name(from)
+                    ));
+                }
+                if !self.state.accept_signed(from, timestamp) {
+                    return Err(anyhow!("Ignored a stale or replayed DM action"));
+                }
+                return self.handle_dm_action(header, action).await;
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Passes an action of a player on to the app, only called when we are the DM
+    async fn handle_player_action(&mut self, from: EndpointId, action: PlayerAction) -> Result<()> {
+        let event = match action {
+            PlayerAction::Initiative { score } => NetworkEvent::InitiativeReceived {
+                from,
+                name: self.state.resolve_name(from).to_owned(),
+                score,
+            },
+            PlayerAction::EndTurn => NetworkEvent::TurnEnded(from),
+            PlayerAction::ChangeStatus { change } => NetworkEvent::StatusChangeReceived {
+                from,
+                name: self.state.resolve_name(from).to_owned(),
+                change,
+            },
+        };
+        self.event_tx
+            .send(SystemEvent::Network(event))
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn handle_dm_action(
+        &mut self,
+        header: MessageHeader,
+        action: DmAction,
+    ) -> Result<ControlFlow<()>> {
+        match action {
+            DmAction::Kick { target } => {
+                if target == self.endpoint.id() {
+                    self.show(
+                        header,
+                        ChatEvent::SystemStatus("The DM removed you from the table".to_string()),
+                    )
                     .await?;
+                    return Ok(ControlFlow::Break(()));
+                }
+                self.state.kicked.insert(target);
+                if let Some(name) = self.state.remove_user(&target) {
+                    self.show(header, ChatEvent::PeerKicked { id: target, name })
+                        .await?;
+                }
+            }
+            DmAction::Snapshot { snapshot, notes } => {
+                if snapshot.is_newer_than(self.state.snapshot_version) {
+                    self.state.snapshot_version = Some(snapshot.version);
+                    self.show(header, ChatEvent::SnapshotReceived { snapshot, notes })
+                        .await?;
+                }
+            }
+            DmAction::History { to, entries } => {
+                if to == self.endpoint.id() {
+                    self.apply_history(entries).await?;
+                }
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Shows the chat messages sent before we joined, skipping the ones we already have
+    async fn apply_history(&mut self, entries: Vec<BacklogEntry>) -> Result<()> {
+        let mut events = Vec::new();
+        for entry in entries {
+            let Ok((header, body)) =
+                Message::from_bytes(&entry.message).and_then(|m| m.open_envelope(&self.key))
+            else {
+                continue;
+            };
+            // the names come from our own peers, anyone in the room can write the history
+            let author = body.author();
+            let name = match self.state.users.contains_key(&author) {
+                true => self.display_name(author),
+                false => author.fmt_short().to_string(),
+            };
+            if header == entry.header
+                && body.is_chat()
+                && self.clock.observe(&header)
+                && self.backlog.insert(entry)
+            {
+                self.state.seen.insert(header.id);
+                events.extend(Self::history_event(body, name).map(|event| (header, event)));
             }
         }
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        self.event_tx
+            .send(SystemEvent::Ui(ChatEvent::SystemStatus(format!(
+                "{} messages sent before you joined:",
+                events.len()
+            ))))
+            .await?;
+        for (header, event) in events {
+            self.show(header, event).await?;
+        }
+        Ok(())
+    }
+
+    /// Shows the event of a received message, for the UI to order and deduplicate
+    async fn show(&self, header: MessageHeader, event: ChatEvent) -> Result<()> {
+        self.event_tx
+            .send(SystemEvent::Ui(ChatEvent::Received {
+                header,
+                event: Box::new(event),
+            }))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// The event showing a chat message sent before we joined, whose rolls we could not seed
+    fn history_event(body: MessageBody, author: String) -> Option<ChatEvent> {
+        let unverified = || Verification::Unverified("rolled before you joined".to_owned());
+        let event = match body {
+            MessageBody::Text { text, .. } => ChatEvent::MessageReceived {
+                author,
+                content: text,
+            },
+            MessageBody::Emote { text, .. } => ChatEvent::Emote { author, text },
+            MessageBody::Ooc { text, .. } => ChatEvent::Ooc {
+                author: Some(author),
+                text,
+            },
+            MessageBody::DiceRoll {
+                from,
+                result,
+                dice,
+                terms,
+                ..
+            } => ChatEvent::DiceRolled {
+                roller: from,
+                result,
+                terms,
+                dice,
+                author: Some(author),
+                verification: unverified(),
+                visibility: RollVisibility::Public,
+            },
+            MessageBody::InlineRolls { from, parts } => ChatEvent::InlineRolled {
+                roller: from,
+                author: Some(author),
+                parts: parts
+                    .into_iter()
+                    .map(|part| match part {
+                        InlinePart::Text(text) => InlineSegment::Text(text),
+                        InlinePart::Roll {
+                            result,
+                            dice,
+                            terms,
+                            ..
+                        } => InlineSegment::Roll {
+                            result,
+                            dice,
+                            terms,
+                            verification: unverified(),
+                        },
+                    })
+                    .collect(),
+            },
+            MessageBody::TableRoll {
+                from, entry, steps, ..
+            } => ChatEvent::TableRolled {
+                roller: from,
+                author: Some(author),
+                entry,
+                steps,
+                verification: unverified(),
+            },
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    /// The name of the peer, marked when they are the DM
+    fn display_name(&self, author: EndpointId) -> String {
+        let name = self.state.resolve_name(author);
+        if author == self.dm {
+            format!("{} (DM)", name)
+        } else {
+            name.to_owned()
+        }
+    }
+
+    fn record(&self, reveal: &Reveal<'_>) -> Result<()> {
+        match &self.recorder {
+            Some(recorder) => recorder.record(reveal),
+            None => Ok(()),
+        }
+    }
+
+    async fn handle_dice_roll(
+        &mut self,
+        header: MessageHeader,
+        reveal: Reveal<'_>,
+        visibility: RollVisibility,
+    ) -> Result<()> {
+        let commitment = self
+            .state
+            .commitments
+            .remove(&(*reveal.roller, reveal.roll_id));
+        self.record(&reveal)?;
+        let verification = reveal.verify(&self.endpoint.id(), commitment.as_ref());
+
+        let name = self.display_name(*reveal.roller);
+        self.show(
+            header,
+            ChatEvent::DiceRolled {
+                roller: *reveal.roller,
+                result: reveal.result,
+                terms: reveal.terms.to_vec(),
+                dice: reveal.dice.clone(),
+                author: Some(name.to_string()),
+                verification,
+                visibility,
+            },
+        )
+        .await?;
         Ok(())
     }
 }
@@ -219,4 +865,18 @@ mod tests {
         assert_eq!(old_name, Some("Alice".to_string()));
         assert_eq!(state.resolve_name(user_id), "Bob");
     }
+
+    #[test]
+    fn test_signed_actions_replay() {
+        let mut state = ChatState::default();
+        let dm = EndpointId::from_bytes(&[0u8; 32]).unwrap();
+        let now = now_millis();
+
+        assert!(state.accept_signed(dm, now));
+        assert!(!state.accept_signed(dm, now));
+        assert!(state.accept_signed(dm, now - 1));
+
+        let stale = now - MAX_SIGNED_AGE.as_millis() as u64 - 1000;
+        assert!(!state.accept_signed(dm, stale));
+    }
 }
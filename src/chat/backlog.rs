@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::message::MessageHeader;
+
+/// Maximum number of chat messages kept for peers joining late
+const MAX_BACKLOG: usize = 200;
+
+/// A chat message as it was sent over gossip, still encrypted with the room key
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BacklogEntry {
+    /// The header of the message, also found inside it once decrypted
+    pub header: MessageHeader,
+    pub message: Vec<u8>,
+}
+
+impl BacklogEntry {
+    pub fn new(header: MessageHeader, message: Vec<u8>) -> Self {
+        Self { header, message }
+    }
+
+    /// The size of the entry in a history response, roughly
+    fn size(&self) -> usize {
+        self.message.len() + 48
+    }
+}
+
+/// The recent chat messages of the room, shared by the client and the backend
+#[derive(Clone, Default)]
+pub struct Backlog {
+    entries: Arc<Mutex<VecDeque<BacklogEntry>>>,
+}
+
+impl Backlog {
+    /// Adds the entry in the order of the message headers, unless it is already there
+    pub fn insert(&self, entry: BacklogEntry) -> bool {
+        let Ok(mut entries) = self.entries.lock() else {
+            return false;
+        };
+        if entries
+            .iter()
+            .any(|other| other.header.id == entry.header.id)
+        {
+            return false;
+        }
+        let index = entries
+            .iter()
+            .rposition(|other| other.header <= entry.header)
+            .map_or(0, |index| index + 1);
+        // older than everything in a full backlog, so it would be evicted straight away
+        if index == 0 && entries.len() >= MAX_BACKLOG {
+            return false;
+        }
+        entries.insert(index, entry);
+        if entries.len() > MAX_BACKLOG {
+            entries.pop_front();
+        }
+        true
+    }
+
+    /// The most recent entries fitting in the size, oldest first
+    pub fn recent(&self, max_size: usize) -> Vec<BacklogEntry> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+        let mut size = 0;
+        let mut recent: Vec<BacklogEntry> = entries
+            .iter()
+            .rev()
+            .take_while(|entry| {
+                size += entry.size();
+                size <= max_size
+            })
+            .cloned()
+            .collect();
+        recent.reverse();
+        recent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(message: &[u8], lamport: u64) -> BacklogEntry {
+        let header = MessageHeader {
+            id: [lamport as u8; 16],
+            ..MessageHeader::new(lamport)
+        };
+        BacklogEntry::new(header, message.to_vec())
+    }
+
+    #[test]
+    fn test_backlog() {
+        let backlog = Backlog::default();
+        assert!(backlog.insert(entry(b"second", 2)));
+        assert!(backlog.insert(entry(b"third", 3)));
+        assert!(backlog.insert(entry(b"first", 1)));
+        assert!(!backlog.insert(entry(b"first", 1)));
+
+        let messages: Vec<Vec<u8>> = backlog
+            .recent(usize::MAX)
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+        );
+
+        let recent = backlog.recent(2 * entry(b"second", 0).size());
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].message, b"second");
+    }
+
+    #[test]
+    fn test_backlog_full() {
+        let backlog = Backlog::default();
+        for lamport in 2..MAX_BACKLOG as u64 + 2 {
+            assert!(backlog.insert(entry(b"newer", lamport)));
+        }
+        assert!(!backlog.insert(entry(b"oldest", 1)));
+        assert!(backlog.insert(entry(b"newest", MAX_BACKLOG as u64 + 2)));
+
+        let recent = backlog.recent(usize::MAX);
+        assert_eq!(recent.len(), MAX_BACKLOG);
+        assert_eq!(recent[0].header.lamport, 3);
+    }
+}
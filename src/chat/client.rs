@@ -1,29 +1,101 @@
-use crate::dice::Dice;
-use crate::message::{Message, MessageBody};
+use crate::chat::{Backlog, BacklogEntry, LamportClock, Recorder};
+use crate::dice::{Dice, Reveal, RollId, RollResult, RollVisibility, Seed};
+use crate::message::{
+    DmAction, InlinePart, MAX_MESSAGE_SIZE, Message, MessageBody, PlayerAction, SealedMessage,
+    now_millis,
+};
+use crate::snapshot::Snapshot;
+use crate::status::StatusChange;
+use crate::tables::TableStep;
 use anyhow::Result;
-use iroh::Endpoint;
-use iroh_gossip::api::GossipSender;
+use iroh::{Endpoint, EndpointId};
+use iroh_gossip::{TopicId, api::GossipSender};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The size of the chat messages sent to a peer joining late, leaving room for the rest
+const MAX_HISTORY_SIZE: usize = MAX_MESSAGE_SIZE * 3 / 4;
 
 #[derive(Clone)]
 pub struct ChatClient {
     sender: GossipSender,
     endpoint: Endpoint,
     key: [u8; 32],
+    dm: EndpointId,
+    /// The room our signed actions are bound to
+    topic: TopicId,
+    recorder: Option<Recorder>,
+    backlog: Backlog,
+    clock: LamportClock,
+    /// The time of our last signed action, kept increasing so that no two share a time
+    signed_at: Arc<AtomicU64>,
 }
 
 impl ChatClient {
-    pub fn new(sender: GossipSender, endpoint: Endpoint, key: [u8; 32]) -> Self {
+    pub fn new(
+        sender: GossipSender,
+        endpoint: Endpoint,
+        key: [u8; 32],
+        dm: EndpointId,
+        topic: TopicId,
+        backlog: Backlog,
+        clock: LamportClock,
+    ) -> Self {
         Self {
             sender,
             endpoint,
             key,
+            dm,
+            topic,
+            recorder: None,
+            backlog,
+            clock,
+            signed_at: Arc::default(),
+        }
+    }
+
+    /// Records every roll we make to the file of the recorder
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    fn sign_dm_action(&self, action: DmAction) -> MessageBody {
+        action.sign(self.endpoint.secret_key(), &self.topic, self.signing_time())
+    }
+
+    fn sign_player_action(&self, action: PlayerAction) -> MessageBody {
+        action.sign(self.endpoint.secret_key(), &self.topic, self.signing_time())
+    }
+
+    /// The current time, or just after our last signed action if the clock did not move on
+    fn signing_time(&self) -> u64 {
+        let now = now_millis();
+        let last = self
+            .signed_at
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+                Some(now.max(last.saturating_add(1)))
+            })
+            .unwrap_or_else(|last| last);
+        now.max(last.saturating_add(1))
+    }
+
+    fn record(&self, reveal: &Reveal<'_>) -> Result<()> {
+        match &self.recorder {
+            Some(recorder) => recorder.record(reveal),
+            None => Ok(()),
         }
     }
 
     async fn broadcast(&self, body: MessageBody) -> Result<()> {
-        let message = Message::new(body, &self.key)?;
+        let is_chat = body.is_chat();
+        let header = self.clock.next_header();
+        let message = Message::with_header(header, body, &self.key)?.to_vec()?;
+        if is_chat {
+            self.backlog
+                .insert(BacklogEntry::new(header, message.clone()));
+        }
         self.sender
-            .broadcast(message.to_vec()?.into())
+            .broadcast(message.into())
             .await
             .map_err(Into::into)
     }
@@ -36,6 +108,36 @@ impl ChatClient {
         self.broadcast(body).await
     }
 
+    pub async fn broadcast_emote(&self, text: String) -> Result<()> {
+        let body = MessageBody::Emote {
+            from: self.endpoint.id(),
+            text,
+        };
+        self.broadcast(body).await
+    }
+
+    /// Sends a text only the recipient can read
+    pub async fn broadcast_whisper(&self, to: EndpointId, text: String) -> Result<()> {
+        let inner = MessageBody::Text {
+            from: self.endpoint.id(),
+            text,
+        };
+        let body = MessageBody::Whisper {
+            from: self.endpoint.id(),
+            to,
+            message: Message::whisper(inner, self.endpoint.secret_key(), &to)?,
+        };
+        self.broadcast(body).await
+    }
+
+    pub async fn broadcast_ooc(&self, text: String) -> Result<()> {
+        let body = MessageBody::Ooc {
+            from: self.endpoint.id(),
+            text,
+        };
+        self.broadcast(body).await
+    }
+
     pub async fn broadcast_join(&self, name: String) -> Result<()> {
         let body = MessageBody::Joined {
             from: self.endpoint.id(),
@@ -44,6 +146,24 @@ impl ChatClient {
         self.broadcast(body).await
     }
 
+    /// Asks for the chat messages sent before we joined
+    pub async fn broadcast_history_request(&self) -> Result<()> {
+        let body = MessageBody::HistoryRequest {
+            from: self.endpoint.id(),
+        };
+        self.broadcast(body).await
+    }
+
+    /// Sends the recent chat messages to a peer joining late
+    pub async fn broadcast_history(&self, to: EndpointId) -> Result<()> {
+        let entries = self.backlog.recent(MAX_HISTORY_SIZE);
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let body = self.sign_dm_action(DmAction::History { to, entries });
+        self.broadcast(body).await
+    }
+
     pub async fn broadcast_left(&self) -> Result<()> {
         let body = MessageBody::Left {
             from: self.endpoint.id(),
@@ -51,17 +171,179 @@ impl ChatClient {
         self.broadcast(body).await
     }
 
+    pub async fn broadcast_inline_rolls(&self, parts: Vec<InlinePart>) -> Result<()> {
+        for part in &parts {
+            if let InlinePart::Roll {
+                roll_id,
+                result,
+                dice,
+                terms,
+                secret,
+                seeds,
+            } = part
+            {
+                self.record(&Reveal {
+                    roller: &self.endpoint.id(),
+                    roll_id: *roll_id,
+                    dice,
+                    result: *result,
+                    terms,
+                    secret,
+                    seeds,
+                })?;
+            }
+        }
+        let body = MessageBody::InlineRolls {
+            from: self.endpoint.id(),
+            parts,
+        };
+        self.broadcast(body).await
+    }
+
+    pub async fn broadcast_macro(&self, name: String, expansion: String) -> Result<()> {
+        let body = MessageBody::MacroShared {
+            from: self.endpoint.id(),
+            name,
+            expansion,
+        };
+        self.broadcast(body).await
+    }
+
+    pub async fn broadcast_table_roll(
+        &self,
+        roll_id: RollId,
+        entry: String,
+        steps: Vec<TableStep>,
+        secret: Seed,
+        seeds: Vec<(EndpointId, Seed)>,
+    ) -> Result<()> {
+        // only the first roll is committed to, the nested ones follow from its seed
+        if let Some(first) = steps.first() {
+            self.record(&Reveal {
+                roller: &self.endpoint.id(),
+                roll_id,
+                dice: &first.dice,
+                result: first.result,
+                terms: &first.terms,
+                secret: &secret,
+                seeds: &seeds,
+            })?;
+        }
+        let body = MessageBody::TableRoll {
+            from: self.endpoint.id(),
+            roll_id,
+            entry,
+            steps,
+            secret,
+            seeds,
+        };
+        self.broadcast(body).await
+    }
+
+    pub fn id(&self) -> EndpointId {
+        self.endpoint.id()
+    }
+
+    pub fn dm(&self) -> EndpointId {
+        self.dm
+    }
+
+    pub fn is_dm(&self) -> bool {
+        self.endpoint.id() == self.dm
+    }
+
+    /// Removes the peer from the table, only listened to when we are the DM
+    pub async fn broadcast_kick(&self, target: EndpointId) -> Result<()> {
+        let body = self.sign_dm_action(DmAction::Kick { target });
+        self.broadcast(body).await
+    }
+
+    /// Sends our initiative to the DM
+    pub async fn broadcast_initiative(&self, score: i64) -> Result<()> {
+        let body = self.sign_player_action(PlayerAction::Initiative { score });
+        self.broadcast(body).await
+    }
+
+    /// Sends a change to a combatant to the DM
+    pub async fn broadcast_status_change(&self, change: StatusChange) -> Result<()> {
+        let body = self.sign_player_action(PlayerAction::ChangeStatus { change });
+        self.broadcast(body).await
+    }
+
+    /// Shares the game state, only listened to when we are the DM
+    pub async fn broadcast_snapshot(&self, snapshot: Snapshot, notes: Vec<String>) -> Result<()> {
+        let body = self.sign_dm_action(DmAction::Snapshot { snapshot, notes });
+        self.broadcast(body).await
+    }
+
+    pub async fn broadcast_end_turn(&self) -> Result<()> {
+        let body = self.sign_player_action(PlayerAction::EndTurn);
+        self.broadcast(body).await
+    }
+
+    pub async fn broadcast_roll_commit(&self, roll_id: RollId, commitment: [u8; 32]) -> Result<()> {
+        let body = MessageBody::RollCommit {
+            from: self.endpoint.id(),
+            roll_id,
+            commitment,
+        };
+        self.broadcast(body).await
+    }
+
+    pub async fn broadcast_roll_seed(
+        &self,
+        roller: EndpointId,
+        roll_id: RollId,
+        seed: Seed,
+    ) -> Result<()> {
+        let body = MessageBody::RollSeed {
+            from: self.endpoint.id(),
+            roller,
+            roll_id,
+            seed,
+        };
+        self.broadcast(body).await
+    }
+
     pub async fn broadcast_dice_roll(
         &self,
-        result: u32,
+        roll_id: RollId,
         dice: Dice,
-        rolls: Vec<u32>,
+        roll: RollResult,
+        secret: Seed,
+        seeds: Vec<(EndpointId, Seed)>,
+        visibility: RollVisibility,
     ) -> Result<()> {
+        // a blind roll is hidden from the roller, so it is left out of their recording
+        if visibility != RollVisibility::Blind {
+            self.record(&Reveal {
+                roller: &self.endpoint.id(),
+                roll_id,
+                dice: &dice,
+                result: roll.total,
+                terms: &roll.terms,
+                secret: &secret,
+                seeds: &seeds,
+            })?;
+        }
         let body = MessageBody::DiceRoll {
             from: self.endpoint.id(),
-            result,
+            roll_id,
+            result: roll.total,
             dice,
-            rolls,
+            terms: roll.terms,
+            secret,
+            seeds,
+        };
+        if visibility == RollVisibility::Public {
+            return self.broadcast(body).await;
+        }
+
+        let body = MessageBody::HiddenRoll {
+            from: self.endpoint.id(),
+            roll_id,
+            visibility,
+            sealed: SealedMessage::seal(&body, &self.dm)?,
         };
         self.broadcast(body).await
     }
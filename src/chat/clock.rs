@@ -0,0 +1,97 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::message::MessageHeader;
+
+/// How far ahead of our clock a received message may be, more than a session ever sends
+const MAX_JUMP: u64 = 1 << 20;
+/// Number of message IDs remembered to skip the messages delivered more than once
+const MAX_SEEN: usize = 4096;
+
+/// The Lamport clock of the room, shared by the client and the backend
+#[derive(Clone, Default)]
+pub struct LamportClock {
+    time: Arc<AtomicU64>,
+}
+
+impl LamportClock {
+    /// Advances the clock for a message we send, returning its header
+    pub fn next_header(&self) -> MessageHeader {
+        let time = self
+            .time
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |time| {
+                Some(time.saturating_add(1))
+            })
+            .unwrap_or_else(|time| time);
+        MessageHeader::new(time.saturating_add(1))
+    }
+
+    /// Moves the clock past a message we received, so that our next one is ordered after it.
+    /// Returns false for a message implausibly far ahead, which is not to be trusted.
+    pub fn observe(&self, header: &MessageHeader) -> bool {
+        self.time
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |time| {
+                (header.lamport <= time.saturating_add(MAX_JUMP))
+                    .then_some(time.max(header.lamport))
+            })
+            .is_ok()
+    }
+}
+
+/// The IDs of the recent messages, to show a message delivered more than once only once
+#[derive(Default)]
+pub struct SeenMessages {
+    ids: HashSet<[u8; 16]>,
+    order: VecDeque<[u8; 16]>,
+}
+
+impl SeenMessages {
+    /// Remembers the ID, returning whether it is new, and forgets the oldest ones
+    pub fn insert(&mut self, id: [u8; 16]) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > MAX_SEEN
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.ids.remove(&oldest);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lamport_clock() {
+        let clock = LamportClock::default();
+        let first = clock.next_header();
+        assert_eq!(first.lamport, 1);
+
+        assert!(clock.observe(&MessageHeader::new(10)));
+        assert!(clock.observe(&MessageHeader::new(4)));
+        let second = clock.next_header();
+        assert_eq!(second.lamport, 11);
+        assert_ne!(first.id, second.id);
+
+        // a forged header far ahead neither moves nor overflows the clock
+        assert!(!clock.observe(&MessageHeader::new(u64::MAX)));
+        assert_eq!(clock.next_header().lamport, 12);
+    }
+
+    #[test]
+    fn test_seen_messages() {
+        let mut seen = SeenMessages::default();
+        assert!(seen.insert([0; 16]));
+        assert!(!seen.insert([0; 16]));
+        for id in 1..=MAX_SEEN {
+            assert!(seen.insert((id as u128).to_le_bytes()));
+        }
+        assert_eq!(seen.ids.len(), MAX_SEEN);
+        assert!(seen.insert([0; 16]));
+    }
+}
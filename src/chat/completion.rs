@@ -0,0 +1,155 @@
+use crate::command::COMMANDS;
+
+const MACRO_SUBCOMMANDS: [&str; 4] = ["add", "list", "remove", "share"];
+
+/// Names offered by Tab completion besides the commands
+pub struct CompletionSources<'a> {
+    pub peers: Vec<&'a str>,
+    pub macros: Vec<&'a str>,
+    pub tables: Vec<String>,
+}
+
+/// A Tab completion of the word before the cursor, cycled through by pressing Tab again
+pub struct Completion {
+    candidates: Vec<String>,
+    selected: usize,
+    /// The input before the completed word
+    before: String,
+    /// The input after the cursor
+    after: String,
+}
+
+impl Completion {
+    /// Finds the candidates for the word before the cursor, given in characters
+    pub fn new(input: &str, cursor: usize, sources: &CompletionSources) -> Option<Self> {
+        let split = input
+            .char_indices()
+            .nth(cursor)
+            .map_or(input.len(), |(index, _)| index);
+        let (head, after) = input.split_at(split);
+        let word_start = head.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+        let (before, word) = head.split_at(word_start);
+
+        let previous: Vec<&str> = before.split_whitespace().collect();
+        let candidates = candidates(&previous, word, sources);
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(Self {
+            candidates,
+            selected: 0,
+            before: before.to_owned(),
+            after: after.to_owned(),
+        })
+    }
+
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Whether there is nothing to cycle through after applying the completion
+    pub fn is_unique(&self) -> bool {
+        self.candidates.len() == 1
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % self.candidates.len();
+    }
+
+    /// The input with the selected candidate in place of the word, and the cursor after it
+    pub fn apply(&self) -> (String, usize) {
+        let mut value = format!("{}{}", self.before, self.candidates[self.selected]);
+        if self.is_unique() && !self.after.starts_with(' ') {
+            value.push(' ');
+        }
+        let cursor = value.chars().count();
+        value.push_str(&self.after);
+        (value, cursor)
+    }
+}
+
+/// The names completing the word, given the words before it
+fn candidates(previous: &[&str], word: &str, sources: &CompletionSources) -> Vec<String> {
+    let names: Vec<String> = match previous {
+        [] if word.starts_with('/') => COMMANDS
+            .iter()
+            .flat_map(|command| std::iter::once(&command.name).chain(command.aliases))
+            .map(|name| name.to_string())
+            .collect(),
+        _ if word.starts_with('@') => sources
+            .peers
+            .iter()
+            .map(|peer| format!("@{}", peer))
+            .collect(),
+        ["/r"] | ["/macro", "remove" | "share"] => {
+            sources.macros.iter().map(|name| name.to_string()).collect()
+        }
+        ["/macro"] => MACRO_SUBCOMMANDS.map(str::to_owned).to_vec(),
+        ["/table"] => sources.tables.clone(),
+        ["/help" | "/?"] => COMMANDS
+            .iter()
+            .map(|command| command.name.to_owned())
+            .collect(),
+        ["/w" | "/whisper" | "/kick"] => {
+            sources.peers.iter().map(|peer| peer.to_string()).collect()
+        }
+        _ if !word.is_empty() => sources.peers.iter().map(|peer| peer.to_string()).collect(),
+        _ => Vec::new(),
+    };
+
+    let word = word.to_lowercase();
+    let mut candidates: Vec<String> = names
+        .into_iter()
+        .filter(|name| name.to_lowercase().starts_with(&word))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(input: &str, sources: &CompletionSources) -> Option<Completion> {
+        Completion::new(input, input.chars().count(), sources)
+    }
+
+    #[test]
+    fn test_completion() {
+        let sources = CompletionSources {
+            peers: vec!["Alice", "Albert", "Bob"],
+            macros: vec!["longsword", "stealth"],
+            tables: vec!["loot".to_owned()],
+        };
+
+        let completion = complete("/ro", &sources).unwrap();
+        assert_eq!(completion.candidates(), ["/roll"]);
+        assert_eq!(completion.apply(), ("/roll ".to_owned(), 6));
+
+        let mut completion = complete("hi al", &sources).unwrap();
+        assert_eq!(completion.candidates(), ["Albert", "Alice"]);
+        completion.next();
+        assert_eq!(completion.apply(), ("hi Alice".to_owned(), 8));
+
+        let completion = complete("/r l", &sources).unwrap();
+        assert_eq!(completion.apply().0, "/r longsword ");
+        assert_eq!(
+            complete("/table ", &sources).unwrap().candidates(),
+            ["loot"]
+        );
+        assert_eq!(
+            complete("ping @b", &sources).unwrap().candidates(),
+            ["@Bob"]
+        );
+        assert!(complete("/roll 1d", &sources).is_none());
+        assert_eq!(complete("/w ", &sources).unwrap().candidates().len(), 3);
+
+        let completion = Completion::new("/ro 1d20", 3, &sources).unwrap();
+        assert_eq!(completion.apply(), ("/roll 1d20".to_owned(), 5));
+    }
+}
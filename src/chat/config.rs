@@ -1,8 +1,9 @@
 use crate::secrets::{get_secret_key, hash_password};
 use crate::ticket::Ticket;
 use anyhow::Result;
-use iroh::{EndpointAddr, SecretKey};
+use iroh::{EndpointAddr, EndpointId, SecretKey};
 use iroh_gossip::TopicId;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use crate::cli::Cli;
@@ -14,6 +15,10 @@ pub struct ChatConfig {
     pub bootstrap_nodes: Vec<EndpointAddr>,
     pub symmetric_key: [u8; 32],
     pub is_host: bool,
+    pub dm: EndpointId,
+    pub tables: PathBuf,
+    /// The file to record every roll of the session to
+    pub record: Option<PathBuf>,
 }
 
 impl ChatConfig {
@@ -24,14 +29,15 @@ impl ChatConfig {
 
         let topic_hash = blake3::hash(cli.room.as_bytes());
 
-        let (topic, bootstrap_nodes, is_host) = match &cli.ticket {
+        // the room creator is the DM, named by the ticket to everyone else
+        let (topic, dm, bootstrap_nodes, is_host) = match &cli.ticket {
             None => {
                 let topic = TopicId::from_bytes(rand::random());
-                (topic, vec![], true)
+                (topic, secret_key.public(), vec![], true)
             }
             Some(ticket) => {
-                let (topic, endpoints) = Ticket::from_str(ticket)?.into_tuple();
-                (topic, endpoints, false)
+                let (topic, dm, endpoints) = Ticket::from_str(ticket)?.into_tuple();
+                (topic, dm, endpoints, false)
             }
         };
 
@@ -44,6 +50,9 @@ impl ChatConfig {
             bootstrap_nodes,
             symmetric_key,
             is_host,
+            dm,
+            tables: cli.tables,
+            record: cli.record,
         })
     }
 }
@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+
+/// Maximum number of lines remembered
+const MAX_HISTORY: usize = 100;
+
+/// The lines sent this session, recalled with Ctrl+Up and Ctrl+Down
+#[derive(Default)]
+pub struct InputHistory {
+    lines: VecDeque<String>,
+    /// The recalled line, counted from the oldest one
+    position: Option<usize>,
+    /// The unsent input to come back to after going through the history
+    draft: String,
+}
+
+impl InputHistory {
+    pub fn push(&mut self, line: String) {
+        self.position = None;
+        if self.lines.back() == Some(&line) {
+            return;
+        }
+        if self.lines.len() == MAX_HISTORY {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Recalls the line before the current one, saving the input when starting to go back
+    pub fn previous(&mut self, input: &str) -> Option<&str> {
+        let position = match self.position {
+            None if self.lines.is_empty() => return None,
+            None => {
+                self.draft = input.to_owned();
+                self.lines.len() - 1
+            }
+            Some(position) => position.saturating_sub(1),
+        };
+        self.position = Some(position);
+        self.lines.get(position).map(String::as_str)
+    }
+
+    /// Recalls the line after the current one, or the saved input after the newest line
+    pub fn next(&mut self) -> Option<&str> {
+        let position = self.position? + 1;
+        if position < self.lines.len() {
+            self.position = Some(position);
+            self.lines.get(position).map(String::as_str)
+        } else {
+            self.position = None;
+            Some(&self.draft)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_history() {
+        let mut history = InputHistory::default();
+        assert_eq!(history.previous("draft"), None);
+
+        history.push("/roll 1d20".to_owned());
+        history.push("hello".to_owned());
+        history.push("hello".to_owned());
+
+        assert_eq!(history.previous("draft"), Some("hello"));
+        assert_eq!(history.previous("hello"), Some("/roll 1d20"));
+        assert_eq!(history.previous("/roll 1d20"), Some("/roll 1d20"));
+        assert_eq!(history.next(), Some("hello"));
+        assert_eq!(history.next(), Some("draft"));
+        assert_eq!(history.next(), None);
+
+        for i in 0..MAX_HISTORY {
+            history.push(i.to_string());
+        }
+        assert_eq!(history.lines.len(), MAX_HISTORY);
+        assert_eq!(history.lines.front().map(String::as_str), Some("0"));
+    }
+}
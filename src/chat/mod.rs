@@ -1,13 +1,24 @@
 mod app;
 mod backend;
+mod backlog;
 mod client;
+mod clock;
+mod completion;
 mod config;
+mod history;
+mod recording;
 mod room;
 mod state;
+mod stats;
 
 pub use app::ChatApp;
 pub use backend::ChatBackend;
+pub use backlog::{Backlog, BacklogEntry};
 pub use client::ChatClient;
+pub use clock::{LamportClock, SeenMessages};
+pub use completion::{Completion, CompletionSources};
 pub use config::ChatConfig;
+pub use recording::Recorder;
 pub use room::ChatRoom;
 pub use state::*;
+pub use stats::{PlayerStats, RollStats};
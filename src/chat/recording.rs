@@ -0,0 +1,143 @@
+use anyhow::{Context, Result, anyhow};
+use iroh::EndpointId;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::dice::{Dice, Reveal, RollId, RollResult, Seed, TermRoll, derive_seed};
+
+/// A revealed roll with the seeds it was rolled from, one JSON object per line of a recording
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RecordedRoll {
+    pub roller: EndpointId,
+    pub roll_id: RollId,
+    pub dice: Dice,
+    pub result: i64,
+    pub terms: Vec<TermRoll>,
+    pub secret: Seed,
+    pub seeds: Vec<(EndpointId, Seed)>,
+}
+
+impl RecordedRoll {
+    /// Rolls the dice again from the recorded seeds
+    pub fn replay(&self) -> RollResult {
+        let seed = derive_seed(&self.roller, self.roll_id, &self.secret, &self.seeds);
+        self.dice.roll_seeded(seed)
+    }
+}
+
+impl From<&Reveal<'_>> for RecordedRoll {
+    fn from(reveal: &Reveal<'_>) -> Self {
+        Self {
+            roller: *reveal.roller,
+            roll_id: reveal.roll_id,
+            dice: reveal.dice.clone(),
+            result: reveal.result,
+            terms: reveal.terms.to_vec(),
+            secret: *reveal.secret,
+            seeds: reveal.seeds.to_vec(),
+        }
+    }
+}
+
+/// Appends every roll of the session to a file, shared by the client and the backend
+#[derive(Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open the recording {}", path.display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn record(&self, reveal: &Reveal<'_>) -> Result<()> {
+        let mut line = serde_json::to_vec(&RecordedRoll::from(reveal))?;
+        line.push(b'\n');
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow!("The recording is poisoned"))?;
+        file.write_all(&line)?;
+        Ok(())
+    }
+}
+
+/// The rolls of a recording that did not come out the same when rolled again
+pub struct Replay {
+    pub rolls: usize,
+    pub mismatches: Vec<(RecordedRoll, RollResult)>,
+}
+
+/// Re-simulates every roll of a recording from its seeds
+pub fn replay(path: &Path) -> Result<Replay> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open the recording {}", path.display()))?;
+
+    let mut replay = Replay {
+        rolls: 0,
+        mismatches: Vec::new(),
+    };
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recorded: RecordedRoll = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid roll on line {}", number + 1))?;
+        let roll = recorded.replay();
+        replay.rolls += 1;
+        if roll.total != recorded.result || roll.terms != recorded.terms {
+            replay.mismatches.push((recorded, roll));
+        }
+    }
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_replay() {
+        let roller = iroh::SecretKey::from_bytes(&[1u8; 32]).public();
+        let peer = iroh::SecretKey::from_bytes(&[2u8; 32]).public();
+        let dice: Dice = "2d20kh1+5".parse().unwrap();
+        let secret = [3; 32];
+        let seeds = vec![(peer, [4; 32])];
+        let roll = dice.roll_seeded(derive_seed(&roller, 9, &secret, &seeds));
+
+        let path =
+            std::env::temp_dir().join(format!("cantrip-replay-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let recorder = Recorder::create(&path).unwrap();
+        let mut reveal = Reveal {
+            roller: &roller,
+            roll_id: 9,
+            dice: &dice,
+            result: roll.total,
+            terms: &roll.terms,
+            secret: &secret,
+            seeds: &seeds,
+        };
+        recorder.record(&reveal).unwrap();
+        reveal.result += 1;
+        recorder.record(&reveal).unwrap();
+
+        let replay = replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.rolls, 2);
+        assert_eq!(replay.mismatches.len(), 1);
+        assert_eq!(replay.mismatches[0].0.result, roll.total + 1);
+        assert_eq!(replay.mismatches[0].1, roll);
+    }
+}
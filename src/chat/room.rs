@@ -1,5 +1,6 @@
-use crate::chat::{ChatBackend, ChatClient, ChatConfig};
+use crate::chat::{Backlog, ChatBackend, ChatClient, ChatConfig, LamportClock, Recorder};
 use crate::events::{ChatEvent, SystemEvent};
+use crate::message::MAX_MESSAGE_SIZE;
 use crate::ticket::Ticket;
 use anyhow::Result;
 use arboard::Clipboard;
@@ -22,13 +23,15 @@ impl ChatRoom {
             .secret_key(config.secret_key)
             .bind()
             .await?;
-        let gossip = Gossip::builder().spawn(endpoint.clone());
+        let gossip = Gossip::builder()
+            .max_message_size(MAX_MESSAGE_SIZE)
+            .spawn(endpoint.clone());
         let router = Router::builder(endpoint.clone())
             .accept(iroh_gossip::ALPN, gossip.clone())
             .spawn();
 
         let clipboard = if config.is_host {
-            let ticket = Ticket::new(config.topic, vec![endpoint.addr()]);
+            let ticket = Ticket::new(config.topic, config.dm, vec![endpoint.addr()]);
             let ticket_str = ticket.to_string();
 
             let (clipboard_msg, clipboard) = spawn_blocking(|| match Clipboard::new() {
@@ -47,6 +50,12 @@ impl ChatRoom {
                 ))))
                 .await?;
 
+            event_tx
+                .send(SystemEvent::Ui(ChatEvent::SystemStatus(
+                    "you are the DM of this table".to_string(),
+                )))
+                .await?;
+
             clipboard
         } else {
             None
@@ -77,18 +86,47 @@ impl ChatRoom {
                 "connected!".to_string(),
             )))
             .await?;
+        event_tx
+            .send(SystemEvent::Ui(ChatEvent::SystemStatus(
+                "rolls marked ✔ seeds match were recomputed from the seeds of every peer, \
+                 but a roller controlling another peer could still steer them"
+                    .to_string(),
+            )))
+            .await?;
 
-        let client = ChatClient::new(sender, endpoint.clone(), config.symmetric_key);
+        let recorder = config.record.as_deref().map(Recorder::create).transpose()?;
+        let backlog = Backlog::default();
+        let clock = LamportClock::default();
+        let mut client = ChatClient::new(
+            sender,
+            endpoint.clone(),
+            config.symmetric_key,
+            config.dm,
+            config.topic,
+            backlog.clone(),
+            clock.clone(),
+        );
+        if let Some(recorder) = &recorder {
+            client.set_recorder(recorder.clone());
+        }
         client.broadcast_join(config.username.clone()).await?;
+        client.broadcast_history_request().await?;
 
-        let backend = ChatBackend::new(
+        let mut backend = ChatBackend::new(
             endpoint,
             config.symmetric_key,
             router,
             receiver,
             event_tx,
             config.username,
+            config.dm,
         );
+        backend.set_backlog(backlog);
+        backend.set_clock(clock);
+        backend.set_topic(config.topic);
+        if let Some(recorder) = recorder {
+            backend.set_recorder(recorder);
+        }
         Ok((client, backend, clipboard))
     }
 }
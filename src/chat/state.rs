@@ -1,23 +1,78 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local};
+use iroh::EndpointId;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tui_input::Input;
 
-use crate::{dice::Dice, events::ChatEvent};
+use crate::{
+    chat::{Completion, CompletionSources, RollStats, SeenMessages, history::InputHistory},
+    dice::{Dice, Odds, RollVisibility, TermRoll, Verification},
+    events::{ChatEvent, InlineSegment},
+    initiative::Initiative,
+    message::MessageHeader,
+    status::TableStatus,
+    tables::TableStep,
+};
 
 #[derive(Clone, PartialEq)]
 pub enum MessageType {
     User(String),
     Me,
     System,
+    Emote(String),
+    Ooc(String),
+    Whisper {
+        peer: String,
+        incoming: bool,
+    },
     Dice {
         user: String,
-        result: u32,
-        rolls: Vec<u32>,
+        result: i64,
+        terms: Vec<TermRoll>,
         dice: Dice,
+        verification: Verification,
+        visibility: RollVisibility,
+    },
+    HiddenDice {
+        user: String,
+        dice: Option<Dice>,
+        visibility: RollVisibility,
     },
+    InlineRolls {
+        user: String,
+        parts: Vec<InlineSegment>,
+    },
+    Table {
+        user: String,
+        entry: String,
+        steps: Vec<TableStep>,
+        verification: Verification,
+    },
+    Odds {
+        dice: Dice,
+        odds: Odds,
+        target: Option<i64>,
+    },
+    Help(Vec<(String, String)>),
 }
 
 pub struct LogMessage {
     pub message_type: MessageType,
     pub content: String,
+    /// When the message was sent, in milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// The header of the message received from the room, None for local ones
+    pub header: Option<MessageHeader>,
+}
+
+impl LogMessage {
+    /// The local time the message was sent, as `21:04`
+    pub fn time(&self) -> String {
+        DateTime::from_timestamp_millis(self.timestamp as i64)
+            .map(|time| time.with_timezone(&Local).format("%H:%M").to_string())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Default)]
@@ -25,6 +80,22 @@ pub struct AppState {
     messages: Vec<LogMessage>,
     input: Input,
     error_popup: Option<String>,
+    stats: RollStats,
+    show_stats: bool,
+    /// Names of the peers in the room, mirroring the backend's users
+    peers: HashMap<EndpointId, String>,
+    completion: Option<Completion>,
+    history: InputHistory,
+    /// The turn order last shared by the DM
+    initiative: Initiative,
+    /// The combatants last shared by the DM
+    status: TableStatus,
+    hide_status: bool,
+    /// Our identity and the DM's, once connected
+    roles: Option<(EndpointId, EndpointId)>,
+    /// IDs of the messages received from the room, shown only once
+    seen: SeenMessages,
+    show_timestamps: bool,
 }
 
 impl AppState {
@@ -40,53 +111,413 @@ impl AppState {
         &mut self.input
     }
 
+    /// Takes the input to send, remembering it in the history
+    pub fn take_input(&mut self) -> String {
+        let value = self.input.value_and_reset();
+        if !value.is_empty() {
+            self.history.push(value.clone());
+        }
+        value
+    }
+
+    pub fn history_previous(&mut self) {
+        if let Some(line) = self.history.previous(self.input.value()) {
+            self.input = Input::new(line.to_owned());
+        }
+    }
+
+    pub fn history_next(&mut self) {
+        if let Some(line) = self.history.next() {
+            self.input = Input::new(line.to_owned());
+        }
+    }
+
     pub fn error_popup(&self) -> Option<&str> {
         self.error_popup.as_deref()
     }
 
+    pub fn stats(&self) -> Option<&RollStats> {
+        self.show_stats.then_some(&self.stats)
+    }
+
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+    }
+
+    pub fn show_timestamps(&self) -> bool {
+        self.show_timestamps
+    }
+
+    pub fn toggle_timestamps(&mut self) {
+        self.show_timestamps = !self.show_timestamps;
+    }
+
+    pub fn set_roles(&mut self, id: EndpointId, dm: EndpointId) {
+        self.roles = Some((id, dm));
+    }
+
+    pub fn toggle_status(&mut self) {
+        self.hide_status = !self.hide_status;
+    }
+
+    /// The players at the table, the DM marked, and the combatants, unless the pane is hidden
+    pub fn table_status(&self) -> Option<(Vec<String>, &TableStatus)> {
+        let (id, dm) = self.roles?;
+        if self.hide_status {
+            return None;
+        }
+        let mark = |peer: EndpointId, name: &str| match peer == dm {
+            true => format!("{} (DM)", name),
+            false => name.to_owned(),
+        };
+        let mut players: Vec<String> = self
+            .peers
+            .iter()
+            .map(|(peer, name)| mark(*peer, name))
+            .collect();
+        players.sort();
+        players.push(mark(id, "You"));
+        Some((players, &self.status))
+    }
+
+    /// The turn order, while there is a combat
+    pub fn initiative(&self) -> Option<&Initiative> {
+        (!self.initiative.is_empty()).then_some(&self.initiative)
+    }
+
+    /// Finds the peer of the name, preferring an exact match over a case-insensitive one
+    pub fn peer_id(&self, name: &str) -> Result<EndpointId> {
+        let exact: Vec<EndpointId> = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| *peer == name)
+            .map(|(id, _)| *id)
+            .collect();
+        let matching = if exact.is_empty() {
+            self.peers
+                .iter()
+                .filter(|(_, peer)| peer.eq_ignore_ascii_case(name))
+                .map(|(id, _)| *id)
+                .collect()
+        } else {
+            exact
+        };
+        match matching.as_slice() {
+            [id] => Ok(*id),
+            [] => Err(anyhow!("Nobody called {} is at the table.", name)),
+            _ => Err(anyhow!("Several players are called {}.", name)),
+        }
+    }
+
+    pub fn completion(&self) -> Option<&Completion> {
+        self.completion.as_ref()
+    }
+
+    /// Completes the word before the cursor, or moves on to the next candidate
+    pub fn complete(&mut self, macros: Vec<&str>, tables: Vec<String>) {
+        match &mut self.completion {
+            Some(completion) => completion.next(),
+            None => {
+                let sources = CompletionSources {
+                    peers: self.peers.values().map(String::as_str).collect(),
+                    macros,
+                    tables,
+                };
+                self.completion =
+                    Completion::new(self.input.value(), self.input.cursor(), &sources);
+            }
+        }
+        let Some(completion) = &self.completion else {
+            return;
+        };
+        let (value, cursor) = completion.apply();
+        self.input = Input::new(value).with_cursor(cursor);
+        if completion.is_unique() {
+            self.completion = None;
+        }
+    }
+
+    pub fn cancel_completion(&mut self) {
+        self.completion = None;
+    }
+
     fn push_log(&mut self, message_type: MessageType, content: String) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis() as u64);
         self.messages.push(LogMessage {
             message_type,
             content,
+            timestamp,
+            header: None,
         });
     }
 
+    /// Applies the event of a message from the room once, moving what it logged in the order
+    /// of the headers. Local messages stay where they were logged.
+    fn apply_received(&mut self, header: MessageHeader, event: ChatEvent) {
+        if !self.seen.insert(header.id) {
+            return;
+        }
+        let start = self.messages.len();
+        self.apply_event(event);
+        let mut logged: Vec<LogMessage> = self.messages.drain(start..).collect();
+        for message in &mut logged {
+            message.timestamp = header.timestamp;
+            message.header = Some(header);
+        }
+        let index = self
+            .messages
+            .iter()
+            .rposition(|message| message.header.is_none_or(|other| other < header))
+            .map_or(0, |index| index + 1);
+        self.messages.splice(index..index, logged);
+    }
+
     pub fn close_error_popup(&mut self) {
         self.error_popup = None;
     }
 
     pub fn apply_event(&mut self, event: ChatEvent) {
         match event {
+            ChatEvent::Received { header, event } => self.apply_received(header, *event),
             ChatEvent::MessageReceived { author, content } => {
                 self.push_log(MessageType::User(author), content);
             }
-            ChatEvent::PeerJoined(name) => {
-                self.push_log(MessageType::System, format!("{name} joined the chat."))
+            ChatEvent::PeerJoined { id, name } => {
+                self.push_log(MessageType::System, format!("{name} joined the chat."));
+                self.peers.insert(id, name);
             }
-            ChatEvent::PeerLeft(name) => {
-                self.push_log(MessageType::System, format!("{name} left the chat."))
+            ChatEvent::PeerLeft { id, name } => {
+                self.push_log(MessageType::System, format!("{name} left the chat."));
+                self.peers.remove(&id);
             }
-            ChatEvent::PeerNameChange { old, new } => self.push_log(
-                MessageType::System,
-                format!("{old} changed their name to '{new}'."),
-            ),
+            ChatEvent::SnapshotReceived { snapshot, notes } => {
+                let initiative = snapshot.initiative;
+                let previous = (
+                    self.initiative.round(),
+                    self.initiative.current().map(|entry| entry.name.clone()),
+                );
+                if initiative.is_empty() && !self.initiative.is_empty() {
+                    self.push_log(MessageType::System, "The combat is over.".to_owned());
+                } else if let Some(current) = initiative.current()
+                    && previous != (initiative.round(), Some(current.name.clone()))
+                {
+                    self.push_log(
+                        MessageType::System,
+                        format!("Round {}: {}'s turn.", initiative.round(), current.name),
+                    );
+                }
+                for note in notes {
+                    self.push_log(MessageType::System, note);
+                }
+                self.initiative = initiative;
+                self.status = snapshot.status;
+            }
+            ChatEvent::PeerKicked { id, name } => {
+                self.push_log(
+                    MessageType::System,
+                    format!("{name} was kicked from the table by the DM."),
+                );
+                self.peers.remove(&id);
+            }
+            ChatEvent::PeerNameChange { id, old, new } => {
+                self.push_log(
+                    MessageType::System,
+                    format!("{old} changed their name to '{new}'."),
+                );
+                self.peers.insert(id, new);
+            }
+            ChatEvent::Whisper {
+                peer,
+                text,
+                incoming,
+            } => self.push_log(MessageType::Whisper { peer, incoming }, text),
             ChatEvent::SystemStatus(text) => self.push_log(MessageType::System, text),
             ChatEvent::DiceRolled {
+                roller,
                 result,
-                rolls,
+                terms,
                 dice,
                 author,
+                verification,
+                visibility,
+            } => {
+                let user = author.unwrap_or_else(|| "You".to_owned());
+                if verification == Verification::Verified {
+                    self.stats.record(roller, &user, &dice, &terms);
+                }
+                self.push_log(
+                    MessageType::Dice {
+                        user,
+                        result,
+                        terms,
+                        dice,
+                        verification,
+                        visibility,
+                    },
+                    "".to_owned(),
+                )
+            }
+            ChatEvent::HiddenRoll {
+                author,
+                dice,
+                visibility,
             } => self.push_log(
-                MessageType::Dice {
+                MessageType::HiddenDice {
                     user: author.unwrap_or_else(|| "You".to_owned()),
-                    result,
-                    rolls,
                     dice,
+                    visibility,
                 },
                 "".to_owned(),
             ),
+            ChatEvent::InlineRolled {
+                roller,
+                author,
+                parts,
+            } => {
+                let user = author.unwrap_or_else(|| "You".to_owned());
+                for part in &parts {
+                    if let InlineSegment::Roll {
+                        dice,
+                        terms,
+                        verification,
+                        ..
+                    } = part
+                        && *verification == Verification::Verified
+                    {
+                        self.stats.record(roller, &user, dice, terms);
+                    }
+                }
+                self.push_log(MessageType::InlineRolls { user, parts }, "".to_owned())
+            }
+            ChatEvent::TableRolled {
+                roller,
+                author,
+                entry,
+                steps,
+                verification,
+            } => {
+                let user = author.unwrap_or_else(|| "You".to_owned());
+                // the nested rolls are not committed to, so only the first one counts
+                if verification == Verification::Verified
+                    && let Some(step) = steps.first()
+                {
+                    self.stats.record(roller, &user, &step.dice, &step.terms);
+                }
+                self.push_log(
+                    MessageType::Table {
+                        user,
+                        entry,
+                        steps,
+                        verification,
+                    },
+                    "".to_owned(),
+                )
+            }
+            ChatEvent::Odds { dice, odds, target } => {
+                self.push_log(MessageType::Odds { dice, odds, target }, "".to_owned())
+            }
+            ChatEvent::Help(commands) => self.push_log(MessageType::Help(commands), "".to_owned()),
             ChatEvent::Error(err_msg) => self.error_popup = Some(err_msg),
             ChatEvent::MessageSent(message) => self.push_log(MessageType::Me, message),
+            ChatEvent::Emote { author, text } => self.push_log(MessageType::Emote(author), text),
+            ChatEvent::Ooc { author, text } => {
+                let user = author.unwrap_or_else(|| "You".to_owned());
+                self.push_log(MessageType::Ooc(user), text)
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received(lamport: u64, content: &str) -> (MessageHeader, ChatEvent) {
+        let header = MessageHeader {
+            id: [lamport as u8; 16],
+            ..MessageHeader::new(lamport)
+        };
+        let event = ChatEvent::MessageReceived {
+            author: "Alice".to_owned(),
+            content: content.to_owned(),
+        };
+        (header, event)
+    }
+
+    fn apply(state: &mut AppState, (header, event): (MessageHeader, ChatEvent)) {
+        state.apply_event(ChatEvent::Received {
+            header,
+            event: Box::new(event),
+        });
+    }
+
+    fn contents(state: &AppState) -> Vec<&str> {
+        state
+            .messages()
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_stats_count_verified_rolls() {
+        let roller = iroh::SecretKey::from_bytes(&[1u8; 32]).public();
+        let dice: Dice = "1d20".parse().unwrap();
+        let roll = dice.roll_seeded([0u8; 32]);
+        let rolled = |verification| ChatEvent::DiceRolled {
+            roller,
+            result: roll.total,
+            terms: roll.terms.clone(),
+            dice: dice.clone(),
+            author: Some("Alice".to_owned()),
+            verification,
+            visibility: RollVisibility::Public,
+        };
+
+        let mut state = AppState::default();
+        state.apply_event(rolled(Verification::Unverified(
+            "no commitment was received".to_owned(),
+        )));
+        assert!(state.stats.players().is_empty());
+
+        state.apply_event(rolled(Verification::Verified));
+        assert_eq!(state.stats.players()[0].rolls, 1);
+
+        // the nested table rolls are not committed to and are left out
+        let step = TableStep {
+            table: "loot".to_owned(),
+            dice: dice.clone(),
+            result: roll.total,
+            terms: roll.terms.clone(),
+        };
+        state.apply_event(ChatEvent::TableRolled {
+            roller,
+            author: Some("Alice".to_owned()),
+            entry: "Gold".to_owned(),
+            steps: vec![step.clone(), step],
+            verification: Verification::Verified,
+        });
+        assert_eq!(state.stats.players()[0].rolls, 2);
+    }
+
+    #[test]
+    fn test_received_messages_order() {
+        let mut state = AppState::default();
+        apply(&mut state, received(1, "first"));
+        apply(&mut state, received(3, "third"));
+        apply(&mut state, received(2, "second"));
+        assert_eq!(contents(&state), ["first", "second", "third"]);
+
+        // redelivered messages are shown once, and messages arriving late stay after ours
+        apply(&mut state, received(2, "second"));
+        state.apply_event(ChatEvent::MessageSent("mine".to_owned()));
+        apply(&mut state, received(4, "fourth"));
+        apply(&mut state, received(0, "late"));
+        assert_eq!(
+            contents(&state),
+            ["first", "second", "third", "mine", "late", "fourth"]
+        );
+    }
+}
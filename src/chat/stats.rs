@@ -0,0 +1,251 @@
+use iroh::EndpointId;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::dice::{Dice, MAX_SIDES, TermRoll};
+
+/// Minimum number of expected rolls of every face for the chi-squared test to be meaningful
+const MIN_EXPECTED: f64 = 5.0;
+
+/// Dice statistics of every player in the session
+#[derive(Default)]
+pub struct RollStats {
+    players: HashMap<EndpointId, PlayerStats>,
+}
+
+impl RollStats {
+    pub fn record(&mut self, roller: EndpointId, name: &str, dice: &Dice, terms: &[TermRoll]) {
+        let player = self.players.entry(roller).or_default();
+        player.name = name.to_owned();
+        player.rolls += 1;
+        for (sides, face, kept) in dice.natural_rolls(terms) {
+            // the rolls come from the network, so neither number is trusted to be in range
+            if sides > MAX_SIDES {
+                continue;
+            }
+            // a die dropped by advantage or disadvantage counts towards fairness but not as a
+            // natural 20 or 1
+            if kept && sides == 20 {
+                match face {
+                    20 => player.natural_20s += 1,
+                    1 => player.natural_1s += 1,
+                    _ => {}
+                }
+            }
+            let counts = player
+                .faces
+                .entry(sides)
+                .or_insert_with(|| vec![0; sides as usize]);
+            if let Some(count) = face
+                .checked_sub(1)
+                .and_then(|index| counts.get_mut(index as usize))
+            {
+                *count += 1;
+            }
+        }
+    }
+
+    /// The players sorted by name
+    pub fn players(&self) -> Vec<&PlayerStats> {
+        let mut players: Vec<&PlayerStats> = self.players.values().collect();
+        players.sort_by(|a, b| a.name.cmp(&b.name));
+        players
+    }
+}
+
+/// The result of a chi-squared goodness of fit test against fair dice
+pub struct Fairness {
+    pub chi_squared: f64,
+    pub degrees_of_freedom: u32,
+    /// Probability of fair dice being at least this far from the expected distribution
+    pub p_value: f64,
+}
+
+#[derive(Default)]
+pub struct PlayerStats {
+    pub name: String,
+    pub rolls: u32,
+    /// How many times each face came up, by the number of sides of the dice
+    faces: BTreeMap<u32, Vec<u32>>,
+    natural_20s: u32,
+    natural_1s: u32,
+}
+
+impl PlayerStats {
+    /// How many times each face of a d20 came up
+    pub fn d20(&self) -> &[u32] {
+        self.faces.get(&20).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn d20_average(&self) -> Option<f64> {
+        let counts = self.d20();
+        let total: u32 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let sum: u32 = (1..).zip(counts).map(|(face, count)| face * count).sum();
+        Some(sum as f64 / total as f64)
+    }
+
+    /// Natural 20s among the kept d20s
+    pub fn natural_20s(&self) -> u32 {
+        self.natural_20s
+    }
+
+    /// Natural 1s among the kept d20s
+    pub fn natural_1s(&self) -> u32 {
+        self.natural_1s
+    }
+
+    /// Tests all dice rolled often enough against fair dice
+    pub fn fairness(&self) -> Option<Fairness> {
+        let mut chi_squared = 0.0;
+        let mut degrees_of_freedom = 0;
+        for (&sides, counts) in &self.faces {
+            let total: u32 = counts.iter().sum();
+            let expected = total as f64 / sides as f64;
+            if expected < MIN_EXPECTED {
+                continue;
+            }
+            chi_squared += counts
+                .iter()
+                .map(|&count| (count as f64 - expected).powi(2) / expected)
+                .sum::<f64>();
+            degrees_of_freedom += sides - 1;
+        }
+        if degrees_of_freedom == 0 {
+            return None;
+        }
+        Some(Fairness {
+            chi_squared,
+            degrees_of_freedom,
+            p_value: upper_gamma(degrees_of_freedom as f64 / 2.0, chi_squared / 2.0),
+        })
+    }
+}
+
+/// The regularized upper incomplete gamma function Q(a, x)
+fn upper_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefix = (a * x.ln() - x - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        // series expansion of the lower function
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..1000 {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term < sum * 1e-15 {
+                break;
+            }
+        }
+        return (1.0 - prefix * sum).clamp(0.0, 1.0);
+    }
+
+    // continued fraction, evaluated with the modified Lentz method
+    let tiny = 1e-300;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / tiny;
+    let mut d = 1.0 / b;
+    let mut fraction = d;
+    for n in 1..1000 {
+        let an = -(n as f64) * (n as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        fraction *= delta;
+        if (delta - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    (prefix * fraction).clamp(0.0, 1.0)
+}
+
+/// The natural logarithm of the gamma function, using the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate() {
+        series += coefficient / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_upper_gamma() {
+        // chi-squared critical values at the 5% level
+        assert_close(upper_gamma(1.0 / 2.0, 3.841459 / 2.0), 0.05);
+        assert_close(upper_gamma(19.0 / 2.0, 30.143527 / 2.0), 0.05);
+        assert_close(upper_gamma(1.0, 2.0), (-2.0f64).exp());
+    }
+
+    #[test]
+    fn test_player_stats() {
+        let roller = iroh::SecretKey::from_bytes(&[1u8; 32]).public();
+        let mut stats = RollStats::default();
+        let dice: Dice = "2d20+1d6".parse().unwrap();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(20);
+        for _ in 0..100 {
+            let roll = dice.roll_with(&mut rng);
+            stats.record(roller, "Alice", &dice, &roll.terms);
+        }
+
+        let player = stats.players()[0];
+        assert_eq!(player.name, "Alice");
+        assert_eq!(player.rolls, 100);
+        assert_eq!(player.d20().iter().sum::<u32>(), 200);
+        assert!((1.0..=20.0).contains(&player.d20_average().unwrap()));
+
+        let fairness = player.fairness().unwrap();
+        assert_eq!(fairness.degrees_of_freedom, 19 + 5);
+        assert!((0.0..=1.0).contains(&fairness.p_value));
+
+        // faces out of range are ignored
+        let d20: Dice = "1d20".parse().unwrap();
+        for value in [0, 21] {
+            let mut roll = d20.roll_with(&mut rng);
+            roll.terms[0].dice[0].value = value;
+            stats.record(roller, "Alice", &d20, &roll.terms);
+        }
+        assert_eq!(stats.players()[0].d20().iter().sum::<u32>(), 200);
+
+        // a natural 1 dropped by advantage is not counted as one
+        let bob = iroh::SecretKey::from_bytes(&[2u8; 32]).public();
+        let adv: Dice = "adv 1d20".parse().unwrap();
+        let mut roll = adv.roll_with(&mut rng);
+        for die in &mut roll.terms[0].dice {
+            die.value = if die.dropped { 1 } else { 20 };
+        }
+        stats.record(bob, "Bob", &adv, &roll.terms);
+        let player = stats.players()[1];
+        assert_eq!(player.d20().iter().sum::<u32>(), 2);
+        assert_eq!((player.natural_20s(), player.natural_1s()), (1, 0));
+    }
+}
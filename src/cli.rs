@@ -2,6 +2,7 @@ use anyhow::Result;
 use anyhow::anyhow;
 use clap::Parser;
 use dialoguer::Input;
+use std::path::PathBuf;
 
 use crate::ticket::TICKET_PREFIX;
 
@@ -19,6 +20,14 @@ struct Args {
     #[arg(short, long)]
     room: Option<String>,
 
+    /// The directory to load random tables for `/table` from
+    #[arg(short, long, default_value = "tables")]
+    tables: PathBuf,
+
+    /// Record every roll of the session with its seeds to a file, to replay later with `/replay`
+    #[arg(long)]
+    record: Option<PathBuf>,
+
     /// Specifies whether you want to open a new chat room or join an existing one
     #[clap(subcommand)]
     command: Command,
@@ -40,6 +49,8 @@ pub struct Cli {
     pub room: String,
     pub password: String,
     pub ticket: Option<String>,
+    pub tables: PathBuf,
+    pub record: Option<PathBuf>,
 }
 
 impl Cli {
@@ -114,6 +125,8 @@ impl TryFrom<Args> for Cli {
             room,
             password,
             ticket,
+            tables: value.tables,
+            record: value.record,
         })
     }
 }
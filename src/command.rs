@@ -1,25 +1,563 @@
+use std::path::PathBuf;
+
+use crate::status::StatusChange;
+
 pub enum InputCommand {
     Quit,
     Broadcast(String),
+    /// A chat message with `[[...]]` rolls embedded in the text
+    InlineRolls(Vec<TextPart>),
     ChangeName(String),
+    Kick(String),
+    Emote(String),
+    Ooc(String),
+    Whisper {
+        to: String,
+        text: String,
+    },
     DiceRoll(String),
+    GmRoll(String),
+    BlindRoll(String),
+    RollMacro(String),
+    AddMacro {
+        name: String,
+        expansion: String,
+    },
+    RemoveMacro(String),
+    ShareMacro(String),
+    ListMacros,
+    Odds {
+        expression: String,
+        target: Option<i64>,
+    },
+    Stats,
+    /// Rolls initiative with the dice, `1d20` by default
+    RollInitiative(String),
+    SetInitiative(i64),
+    /// Adds an NPC to the turn order with a roll or a fixed score
+    AddNpc {
+        name: String,
+        initiative: String,
+    },
+    RemoveInitiative(String),
+    ClearInitiative,
+    NextTurn,
+    /// Changes the HP, conditions or concentration of a combatant
+    ChangeStatus(StatusChange),
+    /// Shows or hides the table status pane
+    ToggleStatus,
+    /// Shows or hides the time messages were sent at
+    ToggleTimestamps,
+    Table(String),
+    /// Shows all commands, or the usage of the named one
+    Help(Option<String>),
+    /// Rolls the dice of a recorded session again from their seeds
+    Replay(PathBuf),
+    /// An unknown command or a command used the wrong way, holding the error to show
+    Invalid(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TextPart {
+    Text(String),
+    Roll(String),
+}
+
+/// How a command takes the text following its name
+enum Argument {
+    None,
+    Required(&'static str),
+    Optional(&'static str),
+}
+
+/// A command known to the chat, with the names it is typed as and how it is used
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    argument: Argument,
+    pub help: &'static str,
+    parse: fn(&str) -> InputCommand,
+}
+
+impl CommandSpec {
+    pub fn usage(&self) -> String {
+        match self.argument {
+            Argument::None => self.name.to_owned(),
+            Argument::Required(argument) => format!("{} {}", self.name, argument),
+            Argument::Optional(argument) => format!("{} [{}]", self.name, argument),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+}
+
+/// Every command, in the order they are listed by `/help`
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "/me",
+        aliases: &["/em"],
+        argument: Argument::Required("<action>"),
+        help: "Describe what your character does, e.g. /me draws her sword",
+        parse: |argument| InputCommand::Emote(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/ooc",
+        aliases: &[],
+        argument: Argument::Required("<text>"),
+        help: "Talk out of character",
+        parse: |argument| InputCommand::Ooc(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/w",
+        aliases: &["/whisper"],
+        argument: Argument::Required("<name> <text>"),
+        help: "Send a message only the named player can read",
+        parse: InputCommand::whisper_command,
+    },
+    CommandSpec {
+        name: "/roll",
+        aliases: &[],
+        argument: Argument::Required("<roll>"),
+        help: "Roll dice for everyone to see, e.g. /roll adv 1d20+5",
+        parse: |argument| InputCommand::DiceRoll(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/gmroll",
+        aliases: &["/gr"],
+        argument: Argument::Required("<roll>"),
+        help: "Roll dice only the DM sees the result of",
+        parse: |argument| InputCommand::GmRoll(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/blindroll",
+        aliases: &["/br"],
+        argument: Argument::Required("<roll>"),
+        help: "Roll dice only the DM sees, hidden from you as well",
+        parse: |argument| InputCommand::BlindRoll(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/r",
+        aliases: &[],
+        argument: Argument::Required("<macro>"),
+        help: "Roll a saved macro",
+        parse: |argument| InputCommand::RollMacro(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/macro",
+        aliases: &[],
+        argument: Argument::Optional(
+            "add <name> <roll> [; <roll>...] | remove <name> | share <name> | list",
+        ),
+        help: "Manage your saved roll macros",
+        parse: InputCommand::macro_command,
+    },
+    CommandSpec {
+        name: "/odds",
+        aliases: &[],
+        argument: Argument::Required("<roll> [vs <number>]"),
+        help: "Show the exact odds of a roll without rolling it",
+        parse: InputCommand::odds_command,
+    },
+    CommandSpec {
+        name: "/init",
+        aliases: &[],
+        argument: Argument::Optional(
+            "<roll> | set <number> | add <npc> <roll or number> | remove <name> | clear",
+        ),
+        help: "Roll initiative, the DM also adds NPCs and ends the combat",
+        parse: InputCommand::initiative_command,
+    },
+    CommandSpec {
+        name: "/next",
+        aliases: &[],
+        argument: Argument::None,
+        help: "Pass the turn to the next combatant, for the DM or on your turn",
+        parse: |_| InputCommand::NextTurn,
+    },
+    CommandSpec {
+        name: "/hp",
+        aliases: &[],
+        argument: Argument::Required(
+            "<name> <-damage | +healing | current/max | temp <number> | remove>",
+        ),
+        help: "Change the hit points of a combatant, e.g. /hp Grog -7",
+        parse: InputCommand::hp_command,
+    },
+    CommandSpec {
+        name: "/cond",
+        aliases: &["/condition"],
+        argument: Argument::Required("add <name> <condition> [rounds] | remove <name> <condition>"),
+        help: "Add or remove a condition, e.g. /cond add goblin1 prone 2",
+        parse: InputCommand::condition_command,
+    },
+    CommandSpec {
+        name: "/conc",
+        aliases: &[],
+        argument: Argument::Required("<name> [spell]"),
+        help: "Start concentrating on a spell, or stop without one",
+        parse: InputCommand::concentration_command,
+    },
+    CommandSpec {
+        name: "/status",
+        aliases: &[],
+        argument: Argument::None,
+        help: "Toggle the pane with the players and combatants",
+        parse: |_| InputCommand::ToggleStatus,
+    },
+    CommandSpec {
+        name: "/table",
+        aliases: &[],
+        argument: Argument::Required("<name>"),
+        help: "Roll on a random table",
+        parse: |argument| InputCommand::Table(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/stats",
+        aliases: &[],
+        argument: Argument::None,
+        help: "Toggle the dice statistics panel",
+        parse: |_| InputCommand::Stats,
+    },
+    CommandSpec {
+        name: "/time",
+        aliases: &[],
+        argument: Argument::None,
+        help: "Toggle the time every message was sent at",
+        parse: |_| InputCommand::ToggleTimestamps,
+    },
+    CommandSpec {
+        name: "/replay",
+        aliases: &[],
+        argument: Argument::Required("<file>"),
+        help: "Roll a session recorded with --record again from its seeds",
+        parse: |argument| InputCommand::Replay(PathBuf::from(argument)),
+    },
+    CommandSpec {
+        name: "/nick",
+        aliases: &["/name"],
+        argument: Argument::Required("<name>"),
+        help: "Change your displayed name",
+        parse: |argument| InputCommand::ChangeName(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/kick",
+        aliases: &[],
+        argument: Argument::Required("<name>"),
+        help: "Remove a player from the table, only for the DM",
+        parse: |argument| InputCommand::Kick(argument.to_owned()),
+    },
+    CommandSpec {
+        name: "/help",
+        aliases: &["/?"],
+        argument: Argument::Optional("<command>"),
+        help: "List the commands, or show how to use one",
+        parse: |argument| InputCommand::Help((!argument.is_empty()).then(|| argument.to_owned())),
+    },
+    CommandSpec {
+        name: "/quit",
+        aliases: &["/exit"],
+        argument: Argument::None,
+        help: "Leave the table",
+        parse: |_| InputCommand::Quit,
+    },
+];
+
+/// Looks up a command by its name or alias, with or without the leading slash
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    let name = name.strip_prefix('/').unwrap_or(name);
+    COMMANDS
+        .iter()
+        .find(|command| command.matches(&format!("/{}", name)))
 }
 
 impl From<String> for InputCommand {
     fn from(value: String) -> Self {
         if !value.starts_with("/") {
-            return Self::Broadcast(value);
+            return match split_inline_rolls(&value) {
+                Some(parts) => Self::InlineRolls(parts),
+                None => Self::Broadcast(value),
+            };
+        }
+        let (name, argument) = value
+            .split_once(char::is_whitespace)
+            .map(|(name, argument)| (name, argument.trim()))
+            .unwrap_or((value.as_str(), ""));
+
+        let Some(command) = find_command(name) else {
+            return Self::Invalid(format!(
+                "Unknown command {}, type /help to list the commands.",
+                name
+            ));
+        };
+        match command.argument {
+            Argument::Required(argument_name) if argument.is_empty() => Self::Invalid(format!(
+                "Missing {}. Usage: {}",
+                argument_name,
+                command.usage()
+            )),
+            Argument::None if !argument.is_empty() => Self::Invalid(format!(
+                "{} takes no argument. Usage: {}",
+                command.name,
+                command.usage()
+            )),
+            _ => (command.parse)(argument),
+        }
+    }
+}
+
+impl InputCommand {
+    fn whisper_command(argument: &str) -> Self {
+        match argument.split_once(char::is_whitespace) {
+            Some((to, text)) if !text.trim().is_empty() => Self::Whisper {
+                to: to.to_owned(),
+                text: text.trim().to_owned(),
+            },
+            _ => Self::Invalid("Missing <text>. Usage: /w <name> <text>".to_owned()),
+        }
+    }
+
+    fn odds_command(argument: &str) -> Self {
+        let Some(index) = argument.to_ascii_lowercase().rfind(" vs ") else {
+            return Self::Odds {
+                expression: argument.to_owned(),
+                target: None,
+            };
+        };
+        match argument[index + 4..].trim().parse() {
+            Ok(target) => Self::Odds {
+                expression: argument[..index].to_owned(),
+                target: Some(target),
+            },
+            Err(_) => Self::Invalid("Usage: /odds <roll> [vs <number>]".to_owned()),
+        }
+    }
+
+    fn initiative_command(argument: &str) -> Self {
+        let (subcommand, rest) = argument
+            .split_once(char::is_whitespace)
+            .map(|(subcommand, rest)| (subcommand, rest.trim()))
+            .unwrap_or((argument, ""));
+        match (subcommand, rest.rsplit_once(char::is_whitespace)) {
+            ("set", None) => match rest.parse() {
+                Ok(score) => Self::SetInitiative(score),
+                Err(_) => Self::Invalid("Usage: /init set <number>".to_owned()),
+            },
+            ("add", Some((name, initiative))) => Self::AddNpc {
+                name: name.trim().to_owned(),
+                initiative: initiative.to_owned(),
+            },
+            ("remove", _) if !rest.is_empty() => Self::RemoveInitiative(rest.to_owned()),
+            ("clear", None) if rest.is_empty() => Self::ClearInitiative,
+            ("", None) => Self::RollInitiative("1d20".to_owned()),
+            ("set" | "add" | "remove" | "clear", _) => Self::Invalid(
+                "Usage: /init <roll> | set <number> | add <npc> <roll or number> | remove <name> | clear"
+                    .to_owned(),
+            ),
+            _ => Self::RollInitiative(argument.to_owned()),
         }
-        match value.split_once(|c: char| c.is_whitespace()) {
-            Some((command, argument)) => match command {
-                "/nick" => Self::ChangeName(argument.to_owned()),
-                "/roll" => Self::DiceRoll(argument.to_owned()),
-                _ => Self::Broadcast(value),
+    }
+
+    fn hp_command(argument: &str) -> Self {
+        let usage = || {
+            Self::Invalid(
+                "Usage: /hp <name> <-damage | +healing | current/max | temp <number> | remove>"
+                    .to_owned(),
+            )
+        };
+        let Some((name, change)) = argument.split_once(char::is_whitespace) else {
+            return usage();
+        };
+        let name = name.to_owned();
+        let change = change.trim();
+        let number = |text: &str| text.trim().parse::<i64>().ok().filter(|n| *n >= 0);
+
+        let change = if change == "remove" {
+            Some(StatusChange::Remove { name })
+        } else if let Some(amount) = change.strip_prefix("temp") {
+            number(amount).map(|amount| StatusChange::TempHp { name, amount })
+        } else if let Some(amount) = change.strip_prefix('-') {
+            number(amount).map(|amount| StatusChange::Damage { name, amount })
+        } else if let Some(amount) = change.strip_prefix('+') {
+            number(amount).map(|amount| StatusChange::Heal { name, amount })
+        } else if let Some((current, max)) = change.split_once('/') {
+            number(current)
+                .zip(number(max))
+                .map(|(current, max)| StatusChange::SetHp { name, current, max })
+        } else {
+            None
+        };
+        change.map_or_else(usage, Self::ChangeStatus)
+    }
+
+    fn condition_command(argument: &str) -> Self {
+        let words: Vec<&str> = argument.split_whitespace().collect();
+        let change = match words.as_slice() {
+            ["add", name, condition] => Some(StatusChange::AddCondition {
+                name: name.to_string(),
+                condition: condition.to_string(),
+                rounds: None,
+            }),
+            ["add", name, condition, rounds] => {
+                rounds
+                    .parse()
+                    .ok()
+                    .map(|rounds| StatusChange::AddCondition {
+                        name: name.to_string(),
+                        condition: condition.to_string(),
+                        rounds: Some(rounds),
+                    })
+            }
+            ["remove", name, condition] => Some(StatusChange::RemoveCondition {
+                name: name.to_string(),
+                condition: condition.to_string(),
+            }),
+            _ => None,
+        };
+        change.map_or_else(
+            || {
+                Self::Invalid(
+                    "Usage: /cond add <name> <condition> [rounds] | remove <name> <condition>"
+                        .to_owned(),
+                )
             },
-            None => match value.as_str() {
-                "/quit" => Self::Quit,
-                _ => Self::Broadcast(value),
+            Self::ChangeStatus,
+        )
+    }
+
+    fn concentration_command(argument: &str) -> Self {
+        let (name, spell) = argument
+            .split_once(char::is_whitespace)
+            .map(|(name, spell)| (name, Some(spell.trim().to_owned())))
+            .unwrap_or((argument, None));
+        Self::ChangeStatus(StatusChange::Concentrate {
+            name: name.to_owned(),
+            spell,
+        })
+    }
+
+    fn macro_command(argument: &str) -> Self {
+        let (subcommand, rest) = argument
+            .split_once(char::is_whitespace)
+            .map(|(subcommand, rest)| (subcommand, rest.trim()))
+            .unwrap_or((argument, ""));
+        match (subcommand, rest.split_once(char::is_whitespace)) {
+            ("add", Some((name, expansion))) => Self::AddMacro {
+                name: name.to_owned(),
+                expansion: expansion.trim().to_owned(),
             },
+            ("remove", None) if !rest.is_empty() => Self::RemoveMacro(rest.to_owned()),
+            ("share", None) if !rest.is_empty() => Self::ShareMacro(rest.to_owned()),
+            ("list" | "", None) => Self::ListMacros,
+            _ => Self::Invalid(
+                "Usage: /macro add <name> <roll> [; <roll>...] | remove <name> | share <name> | list"
+                    .to_owned(),
+            ),
+        }
+    }
+}
+
+/// Splits the text around `[[...]]` rolls, if it contains any
+fn split_inline_rolls(text: &str) -> Option<Vec<TextPart>> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some((before, after)) = rest.split_once("[[")
+        && let Some((roll, after)) = after.split_once("]]")
+    {
+        if !before.is_empty() {
+            parts.push(TextPart::Text(before.to_owned()));
+        }
+        parts.push(TextPart::Roll(roll.trim().to_owned()));
+        rest = after;
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    if !rest.is_empty() {
+        parts.push(TextPart::Text(rest.to_owned()));
+    }
+    Some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(input: &str) -> String {
+        match InputCommand::from(input.to_owned()) {
+            InputCommand::Invalid(error) => error,
+            _ => panic!("Expected {} to be invalid", input),
         }
     }
+
+    #[test]
+    fn test_parse_commands() {
+        assert!(matches!(
+            InputCommand::from("/roll  1d20+5 ".to_owned()),
+            InputCommand::DiceRoll(roll) if roll == "1d20+5"
+        ));
+        assert!(matches!(
+            InputCommand::from("/me draws her sword".to_owned()),
+            InputCommand::Emote(action) if action == "draws her sword"
+        ));
+        assert!(matches!(
+            InputCommand::from("/exit".to_owned()),
+            InputCommand::Quit
+        ));
+        assert!(matches!(
+            InputCommand::from("/macro".to_owned()),
+            InputCommand::ListMacros
+        ));
+        assert!(matches!(
+            InputCommand::from("/help roll".to_owned()),
+            InputCommand::Help(Some(command)) if command == "roll"
+        ));
+
+        assert!(matches!(
+            InputCommand::from("/init add Goblin chief 1d20+2".to_owned()),
+            InputCommand::AddNpc { name, initiative } if name == "Goblin chief" && initiative == "1d20+2"
+        ));
+        assert!(matches!(
+            InputCommand::from("/init".to_owned()),
+            InputCommand::RollInitiative(roll) if roll == "1d20"
+        ));
+
+        assert!(matches!(
+            InputCommand::from("/hp Grog -7".to_owned()),
+            InputCommand::ChangeStatus(StatusChange::Damage { name, amount: 7 }) if name == "Grog"
+        ));
+        assert!(matches!(
+            InputCommand::from("/cond add goblin1 prone 2".to_owned()),
+            InputCommand::ChangeStatus(StatusChange::AddCondition {
+                rounds: Some(2),
+                ..
+            })
+        ));
+        assert!(invalid("/hp Grog 7").starts_with("Usage: /hp"));
+
+        assert!(invalid("/rol 1d20").starts_with("Unknown command /rol"));
+        assert_eq!(invalid("/nick"), "Missing <name>. Usage: /nick <name>");
+        assert_eq!(
+            invalid("/quit now"),
+            "/quit takes no argument. Usage: /quit"
+        );
+        assert!(find_command("gr").is_some_and(|command| command.name == "/gmroll"));
+    }
+
+    #[test]
+    fn test_split_inline_rolls() {
+        assert_eq!(
+            split_inline_rolls("I swing at the goblin [[1d20+5]] for [[ 1d8+3 ]]!"),
+            Some(vec![
+                TextPart::Text("I swing at the goblin ".to_owned()),
+                TextPart::Roll("1d20+5".to_owned()),
+                TextPart::Text(" for ".to_owned()),
+                TextPart::Roll("1d8+3".to_owned()),
+                TextPart::Text("!".to_owned()),
+            ])
+        );
+        assert_eq!(split_inline_rolls("no rolls [[here"), None);
+        assert_eq!(split_inline_rolls("plain text"), None);
+    }
 }
@@ -0,0 +1,1232 @@
+mod odds;
+mod parser;
+mod verify;
+
+use anyhow::{Result, anyhow};
+use rand::distr::{Distribution, Uniform};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+use crate::dice::odds::Calculator;
+pub use crate::dice::odds::Odds;
+use crate::dice::parser::Parser;
+pub use crate::dice::verify::{
+    Commitment, Reveal, RollId, Seed, Verification, commitment, derive_seed,
+};
+
+/// Maximum number of dice a single expression is allowed to roll
+const MAX_DICE: u32 = 500;
+/// Maximum number of sides of a single die
+pub const MAX_SIDES: u32 = 1000;
+/// Maximum length of an explosion chain started by a single die
+const MAX_EXPLOSIONS: usize = 100;
+/// Maximum number of times a single die is rerolled
+const MAX_REROLLS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A condition on the face value of a die, such as `=1` or `>=5`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComparePoint {
+    op: CompareOp,
+    value: u32,
+}
+
+impl ComparePoint {
+    pub fn new(op: CompareOp, value: u32) -> Self {
+        Self { op, value }
+    }
+
+    pub fn matches(&self, face: u32) -> bool {
+        match self.op {
+            CompareOp::Eq => face == self.value,
+            CompareOp::Lt => face < self.value,
+            CompareOp::Le => face <= self.value,
+            CompareOp::Gt => face > self.value,
+            CompareOp::Ge => face >= self.value,
+        }
+    }
+
+    fn matches_any(&self, sides: u32) -> bool {
+        (1..=sides).any(|face| self.matches(face))
+    }
+
+    fn matches_all(&self, sides: u32) -> bool {
+        (1..=sides).all(|face| self.matches(face))
+    }
+}
+
+impl fmt::Display for ComparePoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.op {
+            CompareOp::Eq => write!(f, "{}", self.value),
+            CompareOp::Lt => write!(f, "<{}", self.value),
+            CompareOp::Le => write!(f, "<={}", self.value),
+            CompareOp::Gt => write!(f, ">{}", self.value),
+            CompareOp::Ge => write!(f, ">={}", self.value),
+        }
+    }
+}
+
+/// Rolls an additional die (or adds to the same one when compounding) whenever a die matches
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Explosion {
+    pub compound: bool,
+    pub on: Option<ComparePoint>,
+}
+
+impl Explosion {
+    /// The condition triggering the explosion, exploding on the highest face by default
+    fn trigger(&self, sides: u32) -> ComparePoint {
+        self.on.unwrap_or(ComparePoint::new(CompareOp::Eq, sides))
+    }
+}
+
+impl fmt::Display for Explosion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", if self.compound { "!!" } else { "!" })?;
+        if let Some(on) = self.on {
+            write!(f, "{}", on)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rerolls a die matching the condition, either until it no longer does or only once
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reroll {
+    pub once: bool,
+    pub on: ComparePoint,
+}
+
+impl fmt::Display for Reroll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", if self.once { "ro" } else { "r" }, self.on)
+    }
+}
+
+/// Counts dice matching `success` (minus those matching `failure`) instead of summing them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Target {
+    pub success: ComparePoint,
+    pub failure: Option<ComparePoint>,
+}
+
+impl Target {
+    fn outcome(&self, face: u32) -> Option<Outcome> {
+        if self.success.matches(face) {
+            Some(Outcome::Success)
+        } else if self.failure.is_some_and(|failure| failure.matches(face)) {
+            Some(Outcome::Failure)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // a bare number would merge with the number of sides
+        if self.success.op == CompareOp::Eq {
+            write!(f, "=")?;
+        }
+        write!(f, "{}", self.success)?;
+        if let Some(failure) = self.failure {
+            write!(f, "f{}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a die of a success-counting pool scored a success or a failure
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+/// Whether a die landed in the critical hit or the fumble range of its term
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Critical {
+    Hit,
+    Fumble,
+}
+
+/// Selects which of the rolled dice count towards the result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selector {
+    KeepHighest(u32),
+    KeepLowest(u32),
+    DropHighest(u32),
+    DropLowest(u32),
+}
+
+impl Selector {
+    fn amount(&self) -> u32 {
+        match *self {
+            Self::KeepHighest(n)
+            | Self::KeepLowest(n)
+            | Self::DropHighest(n)
+            | Self::DropLowest(n) => n,
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeepHighest(n) => write!(f, "kh{n}"),
+            Self::KeepLowest(n) => write!(f, "kl{n}"),
+            Self::DropHighest(n) => write!(f, "dh{n}"),
+            Self::DropLowest(n) => write!(f, "dl{n}"),
+        }
+    }
+}
+
+/// The kind of dice rolled by a term
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Sides {
+    /// A regular dice numbered from 1 to N
+    Numeric(u32),
+    /// A Fate/Fudge dice with `-`, blank and `+` faces
+    Fate,
+    /// A percentile dice (`d%`) numbered from 1 to 100
+    Percentile,
+}
+
+impl Sides {
+    /// Number of faces of the dice, which are rolled as values from 1 to this number
+    fn faces(&self) -> u32 {
+        match *self {
+            Self::Numeric(sides) => sides,
+            Self::Fate => 3,
+            Self::Percentile => 100,
+        }
+    }
+
+    /// The value a face contributes to the total
+    pub fn face_value(&self, face: u32) -> i64 {
+        match self {
+            Self::Fate => face as i64 - 2,
+            Self::Numeric(_) | Self::Percentile => face as i64,
+        }
+    }
+
+    pub fn format_face(&self, face: u32) -> String {
+        match (self, self.face_value(face)) {
+            (Self::Fate, 1..) => "+".to_owned(),
+            (Self::Fate, 0) => "▢".to_owned(),
+            (Self::Fate, _) => "−".to_owned(),
+            (Self::Percentile, value @ ..100) => format!("{:02}", value),
+            (_, value) => value.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Sides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Numeric(sides) => write!(f, "{sides}"),
+            Self::Fate => write!(f, "F"),
+            Self::Percentile => write!(f, "%"),
+        }
+    }
+}
+
+/// A single rolled die, marked as dropped when a selector discarded it.
+///
+/// `rerolled` holds the values this die showed before being rerolled, in order, and `exploded`
+/// marks a die that triggered an explosion (for compounding dice, `value` is the whole chain).
+/// In success-counting pools, `outcome` tells whether the die scored a success or a failure,
+/// and `critical` marks a die that landed in the critical hit or fumble range of its term.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct DieRoll {
+    pub value: u32,
+    pub dropped: bool,
+    pub rerolled: Vec<u32>,
+    pub exploded: bool,
+    pub outcome: Option<Outcome>,
+    pub critical: Option<Critical>,
+}
+
+impl DieRoll {
+    /// Displays the die as a face of the given kind of dice
+    pub fn display(&self, sides: Sides) -> DisplayDie<'_> {
+        DisplayDie { die: self, sides }
+    }
+}
+
+pub struct DisplayDie<'a> {
+    die: &'a DieRoll,
+    sides: Sides,
+}
+
+impl fmt::Display for DisplayDie<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { die, sides } = self;
+        if die.dropped {
+            write!(f, "~")?;
+        }
+        for &value in &die.rerolled {
+            write!(f, "{}→", sides.format_face(value))?;
+        }
+        write!(f, "{}", sides.format_face(die.value))?;
+        if die.exploded {
+            write!(f, "!")?;
+        }
+        match die.outcome {
+            Some(Outcome::Success) => write!(f, "*")?,
+            Some(Outcome::Failure) => write!(f, "×")?,
+            None => {}
+        }
+        if die.dropped {
+            write!(f, "~")?;
+        }
+        Ok(())
+    }
+}
+
+/// The dice rolled for a single `NdS` term of an expression
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TermRoll {
+    pub total: i64,
+    pub dice: Vec<DieRoll>,
+}
+
+/// The outcome of rolling a whole expression, with the terms listed in expression order
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollResult {
+    pub total: i64,
+    pub terms: Vec<TermRoll>,
+}
+
+/// Who gets to see the result of a roll
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum RollVisibility {
+    Public,
+    /// Only the roller and the DM see the result
+    Gm,
+    /// Only the DM sees the result, not even the roller
+    Blind,
+}
+
+impl RollVisibility {
+    pub fn label(&self) -> Option<&'static str> {
+        match self {
+            Self::Public => None,
+            Self::Gm => Some("GM roll"),
+            Self::Blind => Some("blind roll"),
+        }
+    }
+}
+
+/// A piece of the human readable breakdown of a roll
+pub enum Segment<'a> {
+    Text(String),
+    Rolls(Sides, &'a [DieRoll]),
+}
+
+/// Optional modifiers changing how the dice of a term are rolled and counted
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct TermModifiers {
+    pub selector: Option<Selector>,
+    pub explosion: Option<Explosion>,
+    pub reroll: Option<Reroll>,
+    pub target: Option<Target>,
+    pub critical: Option<ComparePoint>,
+    pub fumble: Option<ComparePoint>,
+}
+
+/// A single `NdS` term with its modifiers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiceTerm {
+    count: u32,
+    sides: Sides,
+    selector: Option<Selector>,
+    explosion: Option<Explosion>,
+    reroll: Option<Reroll>,
+    target: Option<Target>,
+    critical: Option<ComparePoint>,
+    fumble: Option<ComparePoint>,
+}
+
+impl DiceTerm {
+    pub fn new(count: u32, sides: Sides, modifiers: TermModifiers) -> Result<Self> {
+        let TermModifiers {
+            selector,
+            explosion,
+            reroll,
+            target,
+            critical,
+            fumble,
+        } = modifiers;
+
+        if count > MAX_DICE {
+            return Err(anyhow!(
+                "Too many dice rolls! Please roll {} dice or fewer.",
+                MAX_DICE
+            ));
+        }
+        if let Sides::Numeric(sides) = sides
+            && !(2..=MAX_SIDES).contains(&sides)
+        {
+            return Err(anyhow!("Invalid number of sides for the dice."));
+        }
+        if sides == Sides::Fate && (explosion.is_some() || reroll.is_some() || target.is_some()) {
+            return Err(anyhow!(
+                "Fate dice cannot explode, be rerolled or count successes."
+            ));
+        }
+        if sides == Sides::Fate && (critical.is_some() || fumble.is_some()) {
+            return Err(anyhow!("Fate dice cannot have critical ranges."));
+        }
+        if let Some(selector) = selector
+            && !(1..=count).contains(&selector.amount())
+        {
+            return Err(anyhow!(
+                "Invalid selector: can only keep or drop between 1 and {} dice.",
+                count
+            ));
+        }
+
+        let faces = sides.faces();
+        if let Some(explosion) = explosion {
+            let trigger = explosion.trigger(faces);
+            if !trigger.matches_any(faces) || trigger.matches_all(faces) {
+                return Err(anyhow!(
+                    "Invalid explosion: the dice must be able to both explode and stop exploding."
+                ));
+            }
+        }
+        if let Some(reroll) = reroll
+            && (!reroll.on.matches_any(faces) || reroll.on.matches_all(faces))
+        {
+            return Err(anyhow!(
+                "Invalid reroll: the condition must match some, but not all faces of the dice."
+            ));
+        }
+
+        if let Some(target) = target {
+            if !target.success.matches_any(faces) {
+                return Err(anyhow!("Invalid target: no face of the dice can succeed."));
+            }
+            if let Some(failure) = target.failure
+                && (1..=faces).any(|face| target.success.matches(face) && failure.matches(face))
+            {
+                return Err(anyhow!(
+                    "Invalid target: a face cannot count as both a success and a failure."
+                ));
+            }
+        }
+
+        let term = DiceTerm {
+            count,
+            sides,
+            selector,
+            explosion,
+            reroll,
+            target,
+            critical,
+            fumble,
+        };
+        let (critical, fumble) = term.critical_ranges();
+        if [critical, fumble]
+            .iter()
+            .flatten()
+            .any(|range| !range.matches_any(faces))
+        {
+            return Err(anyhow!(
+                "Invalid critical range: no face of the dice can land in it."
+            ));
+        }
+        if let (Some(critical), Some(fumble)) = (critical, fumble)
+            && (1..=faces).any(|face| critical.matches(face) && fumble.matches(face))
+        {
+            return Err(anyhow!(
+                "Invalid critical range: a face cannot be both a critical hit and a fumble."
+            ));
+        }
+        Ok(term)
+    }
+
+    fn modifiers(&self) -> TermModifiers {
+        TermModifiers {
+            selector: self.selector,
+            explosion: self.explosion,
+            reroll: self.reroll,
+            target: self.target,
+            critical: self.critical,
+            fumble: self.fumble,
+        }
+    }
+
+    /// The critical hit and fumble ranges. Unless asked for with `cs` and `cf`, only a d20 check,
+    /// a single d20 or the one kept with advantage, has them: a natural 20 and a natural 1.
+    fn critical_ranges(&self) -> (Option<ComparePoint>, Option<ComparePoint>) {
+        let is_check = self.sides == Sides::Numeric(20)
+            && match self.selector {
+                None => self.count == 1,
+                Some(Selector::KeepHighest(kept) | Selector::KeepLowest(kept)) => kept == 1,
+                Some(Selector::DropHighest(_) | Selector::DropLowest(_)) => false,
+            };
+        if !is_check {
+            return (self.critical, self.fumble);
+        }
+        (
+            self.critical.or(Some(ComparePoint::new(CompareOp::Eq, 20))),
+            self.fumble.or(Some(ComparePoint::new(CompareOp::Eq, 1))),
+        )
+    }
+
+    fn roll<R: Rng + ?Sized>(&self, rng: &mut R) -> TermRoll {
+        let uniform = Uniform::new_inclusive(1, self.sides.faces())
+            .expect("Unexpected error: roll with a dice with invalid number of sides");
+
+        let mut dice = Vec::with_capacity(self.count as usize);
+        for _ in 0..self.count {
+            let mut die = self.roll_die(&uniform, rng);
+            let Some(explosion) = self.explosion else {
+                dice.push(die);
+                continue;
+            };
+
+            let trigger = explosion.trigger(self.sides.faces());
+            let mut last_value = die.value;
+            for _ in 0..MAX_EXPLOSIONS {
+                if !trigger.matches(last_value) {
+                    break;
+                }
+                die.exploded = true;
+                if explosion.compound {
+                    last_value = uniform.sample(rng);
+                    die.value = die.value.saturating_add(last_value);
+                } else {
+                    dice.push(die);
+                    die = self.roll_die(&uniform, rng);
+                    last_value = die.value;
+                }
+            }
+            dice.push(die);
+        }
+        self.apply_selector(&mut dice);
+        let (critical, fumble) = self.critical_ranges();
+        for die in dice.iter_mut().filter(|die| !die.dropped) {
+            if critical.is_some_and(|critical| critical.matches(die.value)) {
+                die.critical = Some(Critical::Hit);
+            } else if fumble.is_some_and(|fumble| fumble.matches(die.value)) {
+                die.critical = Some(Critical::Fumble);
+            }
+        }
+
+        let kept = dice.iter_mut().filter(|roll| !roll.dropped);
+        let total = match self.target {
+            Some(target) => kept
+                .map(|roll| {
+                    roll.outcome = target.outcome(roll.value);
+                    match roll.outcome {
+                        Some(Outcome::Success) => 1,
+                        Some(Outcome::Failure) => -1,
+                        None => 0,
+                    }
+                })
+                .sum(),
+            None => kept.map(|roll| self.sides.face_value(roll.value)).sum(),
+        };
+        TermRoll { total, dice }
+    }
+
+    /// Rolls a single die, applying the reroll modifier
+    fn roll_die<R: Rng + ?Sized>(&self, uniform: &Uniform<u32>, rng: &mut R) -> DieRoll {
+        let mut die = DieRoll {
+            value: uniform.sample(rng),
+            dropped: false,
+            rerolled: Vec::new(),
+            exploded: false,
+            outcome: None,
+            critical: None,
+        };
+        if let Some(reroll) = self.reroll {
+            let limit = if reroll.once { 1 } else { MAX_REROLLS };
+            while reroll.on.matches(die.value) && die.rerolled.len() < limit {
+                die.rerolled.push(die.value);
+                die.value = uniform.sample(rng);
+            }
+        }
+        die
+    }
+
+    /// Marks the dice discarded by the selector as dropped, keeping the original roll order
+    fn apply_selector(&self, rolls: &mut [DieRoll]) {
+        let Some(selector) = self.selector else {
+            return;
+        };
+        let mut order: Vec<usize> = (0..rolls.len()).collect();
+        order.sort_by_key(|&i| rolls[i].value);
+
+        let total = order.len();
+        let n = (selector.amount() as usize).min(total);
+        let dropped = match selector {
+            Selector::KeepHighest(_) => &order[..total - n],
+            Selector::KeepLowest(_) => &order[n..],
+            Selector::DropHighest(_) => &order[total - n..],
+            Selector::DropLowest(_) => &order[..n],
+        };
+        for &i in dropped {
+            rolls[i].dropped = true;
+        }
+    }
+
+    /// Rolls twice as many dice and keeps the better (advantage) or worse (disadvantage) half
+    fn with_advantage(self, advantage: bool) -> Result<Self> {
+        if self.selector.is_some() {
+            return Err(anyhow!(
+                "Advantage cannot be combined with keep or drop selectors."
+            ));
+        }
+        let selector = if advantage {
+            Selector::KeepHighest(self.count)
+        } else {
+            Selector::KeepLowest(self.count)
+        };
+        DiceTerm::new(
+            self.count.saturating_mul(2),
+            self.sides,
+            TermModifiers {
+                selector: Some(selector),
+                ..self.modifiers()
+            },
+        )
+    }
+}
+
+impl fmt::Display for DiceTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.count, self.sides)?;
+
+        if let Some(reroll) = self.reroll {
+            write!(f, "{}", reroll)?;
+        }
+        // the target goes first, as an explosion would claim its comparison
+        if let Some(target) = self.target {
+            write!(f, "{}", target)?;
+        }
+        if let Some(explosion) = self.explosion {
+            write!(f, "{}", explosion)?;
+        }
+        if let Some(selector) = self.selector {
+            write!(f, "{}", selector)?;
+        }
+        if let Some(critical) = self.critical {
+            write!(f, "cs{}", critical)?;
+        }
+        if let Some(fumble) = self.fumble {
+            write!(f, "cf{}", fumble)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl Operator {
+    fn apply(self, lhs: i64, rhs: i64) -> i64 {
+        match self {
+            Self::Add => lhs.saturating_add(rhs),
+            Self::Sub => lhs.saturating_sub(rhs),
+            Self::Mul => lhs.saturating_mul(rhs),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Add => write!(f, "+"),
+            Self::Sub => write!(f, "-"),
+            Self::Mul => write!(f, "*"),
+        }
+    }
+}
+
+/// The syntax tree of a dice expression
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(u32),
+    Roll(DiceTerm),
+    Neg(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Group(Box<Expr>),
+}
+
+impl Expr {
+    fn roll<R: Rng + ?Sized>(&self, rng: &mut R, terms: &mut Vec<TermRoll>) -> i64 {
+        match self {
+            Self::Number(n) => *n as i64,
+            Self::Roll(term) => {
+                let term_roll = term.roll(rng);
+                let total = term_roll.total;
+                terms.push(term_roll);
+                total
+            }
+            Self::Neg(expr) => expr.roll(rng, terms).saturating_neg(),
+            Self::Binary(op, lhs, rhs) => {
+                let lhs = lhs.roll(rng, terms);
+                let rhs = rhs.roll(rng, terms);
+                op.apply(lhs, rhs)
+            }
+            Self::Group(expr) => expr.roll(rng, terms),
+        }
+    }
+
+    fn dice_count(&self) -> u32 {
+        match self {
+            Self::Number(_) => 0,
+            Self::Roll(term) => term.count,
+            Self::Neg(expr) | Self::Group(expr) => expr.dice_count(),
+            Self::Binary(_, lhs, rhs) => lhs.dice_count().saturating_add(rhs.dice_count()),
+        }
+    }
+
+    fn counts_successes(&self) -> bool {
+        match self {
+            Self::Number(_) => false,
+            Self::Roll(term) => term.target.is_some(),
+            Self::Neg(expr) | Self::Group(expr) => expr.counts_successes(),
+            Self::Binary(_, lhs, rhs) => lhs.counts_successes() || rhs.counts_successes(),
+        }
+    }
+
+    fn first_term_mut(&mut self) -> Option<&mut DiceTerm> {
+        match self {
+            Self::Number(_) => None,
+            Self::Roll(term) => Some(term),
+            Self::Neg(expr) | Self::Group(expr) => expr.first_term_mut(),
+            Self::Binary(_, lhs, rhs) => match lhs.first_term_mut() {
+                Some(term) => Some(term),
+                None => rhs.first_term_mut(),
+            },
+        }
+    }
+
+    fn breakdown<'a>(
+        &self,
+        terms: &mut impl Iterator<Item = &'a TermRoll>,
+        segments: &mut Vec<Segment<'a>>,
+    ) {
+        match self {
+            Self::Number(n) => segments.push(Segment::Text(n.to_string())),
+            Self::Roll(term) => match terms.next() {
+                Some(term_roll) => segments.push(Segment::Rolls(term.sides, &term_roll.dice)),
+                None => segments.push(Segment::Text("[?]".to_owned())),
+            },
+            Self::Neg(expr) => {
+                segments.push(Segment::Text("-".to_owned()));
+                expr.breakdown(terms, segments);
+            }
+            Self::Binary(op, lhs, rhs) => {
+                lhs.breakdown(terms, segments);
+                segments.push(Segment::Text(format!(" {} ", op)));
+                rhs.breakdown(terms, segments);
+            }
+            Self::Group(expr) => {
+                segments.push(Segment::Text("(".to_owned()));
+                expr.breakdown(terms, segments);
+                segments.push(Segment::Text(")".to_owned()));
+            }
+        }
+    }
+
+    fn natural_rolls<'a>(
+        &self,
+        terms: &mut impl Iterator<Item = &'a TermRoll>,
+        rolls: &mut Vec<(u32, u32, bool)>,
+    ) {
+        match self {
+            Self::Number(_) => {}
+            Self::Roll(term) => {
+                let Some(term_roll) = terms.next() else {
+                    return;
+                };
+                let fair = term.reroll.is_none()
+                    && term.explosion.is_none_or(|explosion| !explosion.compound);
+                if fair {
+                    let faces = term.sides.faces();
+                    rolls.extend(
+                        term_roll
+                            .dice
+                            .iter()
+                            .map(|die| (faces, die.value, !die.dropped)),
+                    );
+                }
+            }
+            Self::Neg(expr) | Self::Group(expr) => expr.natural_rolls(terms, rolls),
+            Self::Binary(_, lhs, rhs) => {
+                lhs.natural_rolls(terms, rolls);
+                rhs.natural_rolls(terms, rolls);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{n}"),
+            Self::Roll(term) => write!(f, "{term}"),
+            Self::Neg(expr) => write!(f, "-{expr}"),
+            Self::Binary(op, lhs, rhs) => write!(f, "{lhs}{op}{rhs}"),
+            Self::Group(expr) => write!(f, "({expr})"),
+        }
+    }
+}
+
+/// A parsed dice expression such as `1d8+2d6+4` or `2*(1d6+3)`.
+///
+/// Sent as the text of the expression, so that a received one is parsed with the same limits as
+/// a typed one and is always valid to roll.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(into = "String", try_from = "String")]
+pub struct Dice {
+    expr: Expr,
+}
+
+impl Dice {
+    pub fn new(expr: Expr) -> Result<Self> {
+        if expr.dice_count() > MAX_DICE {
+            return Err(anyhow!(
+                "Too many dice rolls! Please roll {} dice or fewer.",
+                MAX_DICE
+            ));
+        }
+        Ok(Dice { expr })
+    }
+
+    /// Rolls the expression deterministically from the seed, so peers can recompute the roll
+    pub fn roll_seeded(&self, seed: Seed) -> RollResult {
+        self.roll_with(&mut ChaCha20Rng::from_seed(seed))
+    }
+
+    /// Rolls the expression with the given random number generator
+    pub fn roll_with<R: Rng + ?Sized>(&self, rng: &mut R) -> RollResult {
+        let mut terms = Vec::new();
+        let total = self.expr.roll(rng, &mut terms);
+        RollResult { total, terms }
+    }
+
+    /// Computes the exact probability distribution of the results of the expression
+    pub fn odds(&self) -> Result<Odds> {
+        Calculator::new().odds(&self.expr)
+    }
+
+    /// Formats a result of this expression, as a sum or as a number of successes for dice pools
+    pub fn format_result(&self, result: i64) -> String {
+        if !self.expr.counts_successes() {
+            return result.to_string();
+        }
+        match result {
+            1 => "1 success".to_owned(),
+            n => format!("{} successes", n),
+        }
+    }
+
+    /// Splits a roll of this expression into text and dice segments for rendering
+    pub fn breakdown<'a>(&self, terms: &'a [TermRoll]) -> Vec<Segment<'a>> {
+        let mut segments = Vec::new();
+        self.expr.breakdown(&mut terms.iter(), &mut segments);
+        segments
+    }
+
+    /// The faces shown by dice whose modifiers do not skew them away from a fair distribution,
+    /// as the number of faces, the face rolled and whether the die was kept
+    pub fn natural_rolls(&self, terms: &[TermRoll]) -> Vec<(u32, u32, bool)> {
+        let mut rolls = Vec::new();
+        self.expr.natural_rolls(&mut terms.iter(), &mut rolls);
+        rolls
+    }
+
+    /// Applies advantage or disadvantage to the first dice term of the expression
+    fn with_advantage(mut self, advantage: bool) -> Result<Self> {
+        let term = self
+            .expr
+            .first_term_mut()
+            .ok_or_else(|| anyhow!("Advantage requires a dice to roll."))?;
+        *term = term.with_advantage(advantage)?;
+        Dice::new(self.expr)
+    }
+}
+
+impl FromStr for Dice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let (advantage, s) = match s.split_once(char::is_whitespace) {
+            Some((prefix, rest)) if prefix.eq_ignore_ascii_case("adv") => (Some(true), rest),
+            Some((prefix, rest)) if prefix.eq_ignore_ascii_case("dis") => (Some(false), rest),
+            _ => (None, s),
+        };
+
+        let dice = Dice::new(Parser::new(s).parse()?)?;
+        match advantage {
+            Some(advantage) => dice.with_advantage(advantage),
+            None => Ok(dice),
+        }
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+impl From<Dice> for String {
+    fn from(dice: Dice) -> Self {
+        dice.to_string()
+    }
+}
+
+impl TryFrom<String> for Dice {
+    type Error = anyhow::Error;
+
+    fn try_from(expression: String) -> Result<Self> {
+        expression.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A generator with a fixed seed, so every test run rolls the same dice
+    fn rng() -> ChaCha20Rng {
+        ChaCha20Rng::seed_from_u64(0x0c4a_7219)
+    }
+
+    fn term(dice: &Dice) -> DiceTerm {
+        match dice.expr {
+            Expr::Roll(term) => term,
+            _ => panic!("Expected a single dice term"),
+        }
+    }
+
+    #[test]
+    fn test_parse_selectors() {
+        let dice: Dice = "2d20kh1".parse().unwrap();
+        assert_eq!(term(&dice).selector, Some(Selector::KeepHighest(1)));
+
+        let dice: Dice = "4d6dl".parse().unwrap();
+        assert_eq!(term(&dice).selector, Some(Selector::DropLowest(1)));
+        assert_eq!(dice.to_string(), "4d6dl1");
+
+        assert!("2d20kh3".parse::<Dice>().is_err());
+        assert!("2d20kl0".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn test_parse_advantage() {
+        let dice: Dice = "adv 1d20+5".parse().unwrap();
+        assert_eq!(dice.to_string(), "2d20kh1+5");
+
+        let dice: Dice = "dis 1d20".parse().unwrap();
+        assert_eq!(dice.to_string(), "2d20kl1");
+
+        assert!("adv 2d20kh1".parse::<Dice>().is_err());
+        assert!("adv 5".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        for expr in ["1d8+2d6+4", "4d6-1d4", "2*(1d6+3)", "-1d4+(2-3)*2", "d20"] {
+            let dice: Dice = expr.parse().unwrap();
+            assert_eq!(dice, dice.to_string().parse().unwrap());
+        }
+        let dice: Dice = " 1d8 + 2D6 +4 ".parse().unwrap();
+        assert_eq!(dice.to_string(), "1d8+2d6+4");
+
+        assert!("1d8+".parse::<Dice>().is_err());
+        assert!("(1d8".parse::<Dice>().is_err());
+        assert!("1d8)".parse::<Dice>().is_err());
+        assert!("2d".parse::<Dice>().is_err());
+        assert!("300d6+300d6".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn test_parse_pathological() {
+        let chain = format!("1{}", "+1".repeat(20_000));
+        assert!(chain.parse::<Dice>().is_err());
+        let nested = format!("{}1", "-".repeat(20_000));
+        assert!(nested.parse::<Dice>().is_err());
+
+        // the same limits apply to dice received from a peer
+        let bytes = postcard::to_stdvec(&chain).unwrap();
+        assert!(postcard::from_bytes::<Dice>(&bytes).is_err());
+
+        let long = format!("1{}", "+1".repeat(100));
+        assert!(long.parse::<Dice>().is_ok());
+    }
+
+    #[test]
+    fn test_deserialize_validates() {
+        let dice: Dice = "adv 1d20+5".parse().unwrap();
+        let bytes = postcard::to_stdvec(&dice).unwrap();
+        assert_eq!(postcard::from_bytes::<Dice>(&bytes).unwrap(), dice);
+
+        // a peer cannot send dice that could never be typed
+        for invalid in ["1d0", "1000d6", "1d6!<7", "1d6r<7"] {
+            let bytes = postcard::to_stdvec(invalid).unwrap();
+            assert!(postcard::from_bytes::<Dice>(&bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_explode_reroll() {
+        let dice: Dice = "1d6!".parse().unwrap();
+        assert_eq!(
+            term(&dice).explosion,
+            Some(Explosion {
+                compound: false,
+                on: None
+            })
+        );
+
+        for expr in ["1d6!!", "2d10!>=9", "4d6r1kh3", "1d20ro<3", "3d6r<=2!!"] {
+            let dice: Dice = expr.parse().unwrap();
+            assert_eq!(dice.to_string(), expr);
+        }
+        assert_eq!("4d6kh3r1".parse::<Dice>().unwrap().to_string(), "4d6r1kh3");
+
+        assert!("1d6!>=1".parse::<Dice>().is_err());
+        assert!("1d6r<=6".parse::<Dice>().is_err());
+        assert!("1d6r7".parse::<Dice>().is_err());
+        assert!("1d6r".parse::<Dice>().is_err());
+        assert!("1d6!!!".parse::<Dice>().is_err());
+        assert!("1d6r1r2".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn test_roll_explode_reroll() {
+        let dice: Dice = "50d2!".parse().unwrap();
+        let roll = dice.roll_with(&mut rng());
+        let rolls = &roll.terms[0].dice;
+        assert_eq!(rolls.iter().filter(|roll| !roll.exploded).count(), 50);
+        assert!(rolls.iter().all(|roll| roll.exploded == (roll.value == 2)));
+
+        let dice: Dice = "50d2!!".parse().unwrap();
+        let roll = dice.roll_with(&mut rng());
+        assert_eq!(roll.terms[0].dice.len(), 50);
+        assert!(roll.terms[0].dice.iter().all(|roll| roll.value % 2 == 1));
+
+        let dice: Dice = "50d6r<3".parse().unwrap();
+        let roll = dice.roll_with(&mut rng());
+        for die in &roll.terms[0].dice {
+            assert!(die.value >= 3);
+            assert!(die.rerolled.iter().all(|&value| value < 3));
+        }
+
+        let dice: Dice = "50d6ro1".parse().unwrap();
+        let roll = dice.roll_with(&mut rng());
+        assert!(roll.terms[0].dice.iter().all(|die| die.rerolled.len() <= 1));
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        for expr in ["8d10>=7", "5d6>=5f1", "4d6=6", "6d10>7f<=1!"] {
+            let dice: Dice = expr.parse().unwrap();
+            assert_eq!(dice.to_string(), expr);
+        }
+        assert_eq!(
+            term(&"5d6>=5f1".parse().unwrap()).target,
+            Some(Target {
+                success: ComparePoint::new(CompareOp::Ge, 5),
+                failure: Some(ComparePoint::new(CompareOp::Eq, 1)),
+            })
+        );
+
+        assert_eq!(term(&"6d10!>7".parse().unwrap()).target, None);
+
+        assert!("8d10>10".parse::<Dice>().is_err());
+        assert!("5d6>=5f<=5".parse::<Dice>().is_err());
+        assert!("5d6>=".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn test_count_successes() {
+        let dice: Dice = "20d6>=5f1".parse().unwrap();
+        let roll = dice.roll_with(&mut rng());
+        let rolls = &roll.terms[0].dice;
+
+        let successes = rolls.iter().filter(|roll| roll.value >= 5).count() as i64;
+        let failures = rolls.iter().filter(|roll| roll.value == 1).count() as i64;
+        assert_eq!(roll.total, successes - failures);
+        assert!(rolls.iter().all(|roll| match roll.outcome {
+            Some(Outcome::Success) => roll.value >= 5,
+            Some(Outcome::Failure) => roll.value == 1,
+            None => (2..5).contains(&roll.value),
+        }));
+
+        assert_eq!(dice.format_result(1), "1 success");
+        assert_eq!(dice.format_result(3), "3 successes");
+        assert_eq!("1d20".parse::<Dice>().unwrap().format_result(3), "3");
+    }
+
+    #[test]
+    fn test_fate_and_percentile() {
+        let dice: Dice = "4dF+1".parse().unwrap();
+        assert_eq!(dice.to_string(), "4dF+1");
+        let roll = dice.roll_with(&mut rng());
+        assert!((-3..=5).contains(&roll.total));
+
+        let dice: Dice = "d%".parse().unwrap();
+        assert_eq!(dice.to_string(), "1d%");
+        assert_eq!(term(&dice).sides.faces(), 100);
+        assert!("1d%<=45".parse::<Dice>().is_ok());
+
+        assert_eq!(Sides::Fate.format_face(1), "−");
+        assert_eq!(Sides::Fate.format_face(3), "+");
+        assert_eq!(Sides::Percentile.format_face(7), "07");
+        assert_eq!(Sides::Percentile.format_face(100), "100");
+
+        assert!("4dF!".parse::<Dice>().is_err());
+        assert!("4dF>=1".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn test_critical_ranges() {
+        for expr in ["1d20cs>=19", "1d20cf<=2", "2d20kh1cs>=19cf1", "1d%cs>95"] {
+            let dice: Dice = expr.parse().unwrap();
+            assert_eq!(dice.to_string(), expr);
+        }
+
+        // only a d20 check has critical hits and fumbles unless asked for
+        for expr in ["1d20+5", "adv 1d20", "dis 1d20"] {
+            let dice: Dice = expr.parse().unwrap();
+            for _ in 0..100 {
+                let roll = dice.roll_with(&mut rng());
+                assert!(roll.terms[0].dice.iter().all(|die| match die.critical {
+                    _ if die.dropped => die.critical.is_none(),
+                    Some(Critical::Hit) => die.value == 20,
+                    Some(Critical::Fumble) => die.value == 1,
+                    None => (2..20).contains(&die.value),
+                }));
+            }
+        }
+        for expr in ["100d20", "100d6", "20d%", "3d20kh2"] {
+            let roll = expr.parse::<Dice>().unwrap().roll_with(&mut rng());
+            assert!(roll.terms[0].dice.iter().all(|die| die.critical.is_none()));
+        }
+
+        let dice: Dice = "100d20cs>=19".parse().unwrap();
+        let roll = dice.roll_with(&mut rng());
+        assert!(
+            roll.terms[0]
+                .dice
+                .iter()
+                .all(|die| (die.critical == Some(Critical::Hit)) == (die.value >= 19))
+        );
+        assert!(
+            roll.terms[0]
+                .dice
+                .iter()
+                .all(|die| die.critical != Some(Critical::Fumble))
+        );
+
+        let roll = "4dF".parse::<Dice>().unwrap().roll_with(&mut rng());
+        assert!(roll.terms[0].dice.iter().all(|die| die.critical.is_none()));
+
+        assert!("1d20cs>20".parse::<Dice>().is_err());
+        assert!("1d20cs>=10cf<=10".parse::<Dice>().is_err());
+        assert!("1d20cs19cs20".parse::<Dice>().is_err());
+        assert!("4dFcs3".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        let dice: Dice = "2+3*4".parse().unwrap();
+        assert_eq!(dice.roll_with(&mut rng()).total, 14);
+
+        let dice: Dice = "(2+3)*4-30".parse().unwrap();
+        assert_eq!(dice.roll_with(&mut rng()).total, -10);
+
+        let dice: Dice = "10-2-3".parse().unwrap();
+        assert_eq!(dice.roll_with(&mut rng()).total, 5);
+    }
+
+    #[test]
+    fn test_roll_terms() {
+        let dice: Dice = "1d8+2d6+4".parse().unwrap();
+        let roll = dice.roll_with(&mut rng());
+
+        assert_eq!(roll.terms.len(), 2);
+        assert_eq!(roll.terms[0].dice.len(), 1);
+        assert_eq!(roll.terms[1].dice.len(), 2);
+        assert_eq!(
+            roll.total,
+            roll.terms.iter().map(|term| term.total).sum::<i64>() + 4
+        );
+    }
+
+    #[test]
+    fn test_roll_drops_dice() {
+        let dice: Dice = "4d6kh3".parse().unwrap();
+        let roll = dice.roll_with(&mut rng());
+        let rolls = &roll.terms[0].dice;
+
+        let dropped: Vec<&DieRoll> = rolls.iter().filter(|roll| roll.dropped).collect();
+        assert_eq!(dropped.len(), 1);
+        assert!(rolls.iter().all(|roll| roll.value >= dropped[0].value));
+        assert_eq!(
+            roll.total,
+            rolls.iter().map(|roll| roll.value as i64).sum::<i64>() - dropped[0].value as i64
+        );
+    }
+
+    #[test]
+    fn test_roll_deterministic() {
+        let dice: Dice = "4d6kh3+1d20!+2".parse().unwrap();
+        assert_eq!(dice.roll_with(&mut rng()), dice.roll_with(&mut rng()));
+        assert_eq!(
+            dice.roll_seeded([7; 32]),
+            dice.roll_with(&mut ChaCha20Rng::from_seed([7; 32]))
+        );
+
+        // peers recompute each other's rolls, so the same seed must keep giving the same dice
+        let roll = "3d6+1d20".parse::<Dice>().unwrap().roll_seeded([7; 32]);
+        let faces: Vec<u32> = roll
+            .terms
+            .iter()
+            .flat_map(|term| term.dice.iter().map(|die| die.value))
+            .collect();
+        assert_eq!(faces, [3, 2, 5, 9]);
+        assert_eq!(roll.total, 19);
+    }
+
+    #[test]
+    fn test_roll_distribution() {
+        let dice: Dice = "1d6".parse().unwrap();
+        let mut rng = rng();
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            counts[dice.roll_with(&mut rng).total as usize - 1] += 1;
+        }
+        assert!(
+            counts.iter().all(|count| (850..1150).contains(count)),
+            "{:?}",
+            counts
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::dice::{DiceTerm, Expr, MAX_EXPLOSIONS, Outcome, Selector};
+
+/// Maximum number of steps computing a distribution may take before giving up
+const MAX_WORK: u64 = 5_000_000;
+
+type Distribution = BTreeMap<i64, f64>;
+
+/// The exact probability distribution of the results of a dice expression
+#[derive(Clone, Debug, PartialEq)]
+pub struct Odds {
+    distribution: Vec<(i64, f64)>,
+}
+
+impl Odds {
+    pub fn min(&self) -> i64 {
+        self.distribution.first().map_or(0, |&(value, _)| value)
+    }
+
+    pub fn max(&self) -> i64 {
+        self.distribution.last().map_or(0, |&(value, _)| value)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.distribution
+            .iter()
+            .map(|&(value, p)| value as f64 * p)
+            .sum()
+    }
+
+    /// Probability of rolling the target or more
+    pub fn at_least(&self, target: i64) -> f64 {
+        self.distribution
+            .iter()
+            .filter(|&&(value, _)| value >= target)
+            .map(|&(_, p)| p)
+            .sum()
+    }
+
+    /// Groups the distribution into at most `rows` ranges of results of equal width
+    pub fn histogram(&self, rows: usize) -> Vec<(i64, i64, f64)> {
+        let span = (self.max() - self.min()) as u64 + 1;
+        let width = span.div_ceil(rows.max(1) as u64) as i64;
+
+        let mut histogram: Vec<(i64, i64, f64)> = Vec::new();
+        for &(value, p) in &self.distribution {
+            let low = self.min() + (value - self.min()) / width * width;
+            match histogram.last_mut() {
+                Some((last_low, _, total)) if *last_low == low => *total += p,
+                _ => histogram.push((low, (low + width - 1).min(self.max()), p)),
+            }
+        }
+        histogram
+    }
+}
+
+/// Computes distributions while keeping track of the amount of work done
+pub(super) struct Calculator {
+    work: u64,
+}
+
+impl Calculator {
+    pub(super) fn new() -> Self {
+        Self { work: 0 }
+    }
+
+    pub(super) fn odds(mut self, expr: &Expr) -> Result<Odds> {
+        let distribution = self.expr(expr)?;
+        Ok(Odds {
+            distribution: distribution.into_iter().filter(|&(_, p)| p > 0.0).collect(),
+        })
+    }
+
+    fn charge(&mut self, work: usize) -> Result<()> {
+        self.work = self.work.saturating_add(work as u64);
+        if self.work > MAX_WORK {
+            return Err(anyhow!(
+                "The expression is too complex to compute the odds exactly."
+            ));
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<Distribution> {
+        match expr {
+            Expr::Number(n) => Ok(Distribution::from([(*n as i64, 1.0)])),
+            Expr::Roll(term) => self.term(term),
+            Expr::Neg(expr) => Ok(self
+                .expr(expr)?
+                .into_iter()
+                .map(|(value, p)| (value.saturating_neg(), p))
+                .collect()),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.expr(lhs)?;
+                let rhs = self.expr(rhs)?;
+                self.combine(&lhs, &rhs, |a, b| op.apply(a, b))
+            }
+            Expr::Group(expr) => self.expr(expr),
+        }
+    }
+
+    fn combine(
+        &mut self,
+        lhs: &Distribution,
+        rhs: &Distribution,
+        op: impl Fn(i64, i64) -> i64,
+    ) -> Result<Distribution> {
+        self.charge(lhs.len().saturating_mul(rhs.len()))?;
+        let mut result = Distribution::new();
+        for (&a, &p) in lhs {
+            for (&b, &q) in rhs {
+                *result.entry(op(a, b)).or_default() += p * q;
+            }
+        }
+        Ok(result)
+    }
+
+    fn term(&mut self, term: &DiceTerm) -> Result<Distribution> {
+        let compound = term.explosion.is_none_or(|explosion| explosion.compound);
+        let score = |value: u32| match term.target {
+            Some(target) => match target.outcome(value) {
+                Some(Outcome::Success) => 1,
+                Some(Outcome::Failure) => -1,
+                None => 0,
+            },
+            None => term.sides.face_value(value),
+        };
+
+        let Some(selector) = term.selector else {
+            // every die scores on its own, so the term is the sum of independent dice
+            let die = if compound {
+                self.die(term, false)?.into_iter().fold(
+                    Distribution::new(),
+                    |mut die, (value, p)| {
+                        *die.entry(score(value as u32)).or_default() += p;
+                        die
+                    },
+                )
+            } else {
+                self.die(term, true)?
+            };
+            let mut total = Distribution::from([(0, 1.0)]);
+            for _ in 0..term.count {
+                total = self.combine(&total, &die, i64::saturating_add)?;
+            }
+            return Ok(total);
+        };
+
+        if !compound {
+            return Err(anyhow!(
+                "Odds of exploding dice cannot be combined with a selector, try compounding (!!)."
+            ));
+        }
+        let die = self.die(term, false)?;
+        self.select(&die, term.count, selector, |value| score(value as u32))
+    }
+
+    /// The distribution of a single die including its explosions, as the total value of the
+    /// chain, or as the sum of the scores of the dice in the chain when `score_each` is set
+    fn die(&mut self, term: &DiceTerm, score_each: bool) -> Result<Distribution> {
+        let faces = term.sides.faces();
+        let uniform = 1.0 / faces as f64;
+        let step = |face: u32| match term.target {
+            Some(target) if score_each => match target.outcome(face) {
+                Some(Outcome::Success) => 1,
+                Some(Outcome::Failure) => -1,
+                None => 0,
+            },
+            _ if score_each => term.sides.face_value(face),
+            _ => face as i64,
+        };
+
+        // the faces a die shows after the rerolls
+        let mut first = vec![0.0; faces as usize + 1];
+        for face in 1..=faces {
+            match term.reroll {
+                Some(reroll) if reroll.on.matches(face) && reroll.once => {
+                    for new_face in 1..=faces {
+                        first[new_face as usize] += uniform * uniform;
+                    }
+                }
+                Some(reroll) if reroll.on.matches(face) => {}
+                _ => first[face as usize] += uniform,
+            }
+        }
+        let total: f64 = first.iter().sum();
+        first.iter_mut().for_each(|p| *p /= total);
+
+        let Some(explosion) = term.explosion else {
+            return Ok((1..=faces)
+                .map(|face| (step(face), first[face as usize]))
+                .fold(Distribution::new(), |mut die, (value, p)| {
+                    *die.entry(value).or_default() += p;
+                    die
+                }));
+        };
+
+        // compounding dice add plain rolls, while exploded dice are rerolled like the first one
+        let next: Vec<f64> = if explosion.compound {
+            (0..=faces)
+                .map(|face| if face == 0 { 0.0 } else { uniform })
+                .collect()
+        } else {
+            first.clone()
+        };
+        let trigger = explosion.trigger(faces);
+
+        let mut result = Distribution::new();
+        let mut pending = Distribution::from([(0, 1.0)]);
+        for (depth, probabilities) in std::iter::once(&first)
+            .chain(std::iter::repeat(&next))
+            .enumerate()
+        {
+            self.charge(pending.len() * faces as usize)?;
+            let mut exploding = Distribution::new();
+            for (&value, &p) in &pending {
+                for face in 1..=faces {
+                    let q = p * probabilities[face as usize];
+                    let value = value.saturating_add(step(face));
+                    if trigger.matches(face) && depth < MAX_EXPLOSIONS {
+                        *exploding.entry(value).or_default() += q;
+                    } else {
+                        *result.entry(value).or_default() += q;
+                    }
+                }
+            }
+            pending = exploding;
+            if pending.values().sum::<f64>() < f64::EPSILON {
+                break;
+            }
+        }
+        for (value, p) in pending {
+            *result.entry(value).or_default() += p;
+        }
+        Ok(result)
+    }
+
+    /// The distribution of the sum of the scores of the dice kept by the selector
+    fn select(
+        &mut self,
+        die: &Distribution,
+        count: u32,
+        selector: Selector,
+        score: impl Fn(i64) -> i64,
+    ) -> Result<Distribution> {
+        let (keep, highest) = match selector {
+            Selector::KeepHighest(n) => (n, true),
+            Selector::KeepLowest(n) => (n, false),
+            Selector::DropHighest(n) => (count - n, false),
+            Selector::DropLowest(n) => (count - n, true),
+        };
+        let mut values: Vec<(i64, f64)> = die.iter().map(|(&value, &p)| (value, p)).collect();
+        if highest {
+            values.reverse();
+        }
+
+        // goes through the values from the most preferred one, distributing the dice not yet
+        // assigned a value, until enough dice are kept
+        let mut result = Distribution::new();
+        let mut states: HashMap<(u32, i64), f64> = HashMap::from([((count, 0), 1.0)]);
+        let mut remaining_mass = 1.0;
+        for (value, p) in values {
+            let q = (p / remaining_mass).clamp(0.0, 1.0);
+            remaining_mass -= p;
+
+            let mut next_states = HashMap::new();
+            for ((remaining, sum), probability) in states {
+                let kept = count - remaining;
+                if kept >= keep {
+                    *result.entry(sum).or_default() += probability;
+                    continue;
+                }
+                self.charge(remaining as usize + 1)?;
+                for (showing, binomial) in binomial(remaining, q).into_iter().enumerate() {
+                    let showing = showing as u32;
+                    let newly_kept = showing.min(keep - kept) as i64;
+                    let sum = sum.saturating_add(newly_kept.saturating_mul(score(value)));
+                    *next_states.entry((remaining - showing, sum)).or_default() +=
+                        probability * binomial;
+                }
+            }
+            states = next_states;
+        }
+        for ((_, sum), probability) in states {
+            *result.entry(sum).or_default() += probability;
+        }
+        Ok(result)
+    }
+}
+
+/// Probabilities of 0 to n successes out of n trials
+fn binomial(n: u32, p: f64) -> Vec<f64> {
+    if p >= 1.0 {
+        let mut probabilities = vec![0.0; n as usize + 1];
+        probabilities[n as usize] = 1.0;
+        return probabilities;
+    }
+    let mut probabilities = Vec::with_capacity(n as usize + 1);
+    let mut probability = (1.0 - p).powi(n as i32);
+    for k in 0..=n {
+        probabilities.push(probability);
+        probability *= (n - k) as f64 / (k + 1) as f64 * p / (1.0 - p);
+    }
+    probabilities
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dice::Dice;
+
+    fn odds(expr: &str) -> super::Odds {
+        expr.parse::<Dice>().unwrap().odds().unwrap()
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_odds_sums() {
+        let attack = odds("1d20+5");
+        assert_eq!((attack.min(), attack.max()), (6, 25));
+        assert_close(attack.mean(), 15.5);
+        assert_close(attack.at_least(17), 0.45);
+
+        let damage = odds("2d6");
+        assert_close(damage.at_least(7), 21.0 / 36.0);
+        assert_close(damage.mean(), 7.0);
+
+        assert_close(odds("2*(1d4)-1").mean(), 4.0);
+        assert_close(odds("4dF").mean(), 0.0);
+    }
+
+    #[test]
+    fn test_odds_modifiers() {
+        assert_close(
+            odds("adv 1d20").at_least(20),
+            1.0 - (19.0f64 / 20.0).powi(2),
+        );
+        assert_close(odds("dis 1d20").at_least(20), 1.0 / 400.0);
+        assert_close(odds("4d6dl1").mean(), 12.244598765432098);
+        assert_close(odds("1d6r1").mean(), 4.0);
+        assert_close(odds("1d6ro1").mean(), 3.5 + 2.5 / 6.0);
+        assert_close(odds("1d6!").mean(), 4.2);
+        assert_close(odds("1d6!!").mean(), 4.2);
+        assert_close(odds("3d6>=5").mean(), 1.0);
+        assert_close(odds("1d10>=8f1").mean(), 0.2);
+
+        assert!("4d6!kh3".parse::<Dice>().unwrap().odds().is_err());
+        assert!("500d1000".parse::<Dice>().unwrap().odds().is_err());
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = odds("1d20").histogram(5);
+        assert_eq!(histogram.len(), 5);
+        assert_eq!((histogram[0].0, histogram[0].1), (1, 4));
+        assert_close(histogram[4].2, 0.2);
+    }
+}
@@ -0,0 +1,312 @@
+use anyhow::{Result, anyhow};
+
+use crate::dice::{
+    CompareOp, ComparePoint, DiceTerm, Explosion, Expr, Operator, Reroll, Selector, Sides, Target,
+    TermModifiers,
+};
+
+/// Maximum nesting depth of parenthesized groups
+const MAX_DEPTH: usize = 32;
+/// Maximum number of nodes of an expression, which also bounds how deep chains of operators
+/// nest, as the syntax tree is walked recursively
+const MAX_NODES: usize = 256;
+
+/// A recursive descent parser for dice expressions.
+///
+/// ```text
+/// expression := product (('+' | '-') product)*
+/// product    := unary ('*' unary)*
+/// unary      := '-' unary | atom
+/// atom       := number | dice | '(' expression ')'
+/// dice       := number? 'd' (number | 'F' | '%') modifier*
+/// modifier   := selector | explosion | reroll | target | critical
+/// critical   := ('cs' | 'cf') compare
+/// selector   := ('kh' | 'kl' | 'k' | 'dh' | 'dl') number?
+/// explosion  := ('!!' | '!') compare?
+/// reroll     := ('ro' | 'r') compare
+/// target     := compare_op number ('f' compare)?
+/// compare    := compare_op? number
+/// compare_op := '=' | '<' | '<=' | '>' | '>='
+/// ```
+pub(super) struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    depth: usize,
+    nodes: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(super) fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            depth: 0,
+            nodes: 0,
+        }
+    }
+
+    pub(super) fn parse(mut self) -> Result<Expr> {
+        let expr = self.expression()?;
+        self.skip_whitespace();
+        match self.rest().chars().next() {
+            None => Ok(expr),
+            Some(c) => Err(anyhow!("Invalid dice format: unexpected '{}'.", c)),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = if self.eat_token("+") {
+                Operator::Add
+            } else if self.eat_token("-") {
+                Operator::Sub
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.product()?;
+            lhs = self.node(Expr::Binary(op, Box::new(lhs), Box::new(rhs)))?;
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while self.eat_token("*") {
+            let rhs = self.unary()?;
+            lhs = self.node(Expr::Binary(Operator::Mul, Box::new(lhs), Box::new(rhs)))?;
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_token("-") {
+            let expr = Expr::Neg(Box::new(self.nested(Self::unary)?));
+            return self.node(expr);
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr> {
+        if self.eat_token("(") {
+            let expr = self.nested(Self::expression)?;
+            if !self.eat_token(")") {
+                return Err(anyhow!("Invalid dice format: missing ')'."));
+            }
+            return self.node(Expr::Group(Box::new(expr)));
+        }
+
+        self.skip_whitespace();
+        let count = self.number()?;
+        let expr = if self.eat("d") {
+            self.dice(count)?
+        } else {
+            count
+                .map(Expr::Number)
+                .ok_or_else(|| anyhow!("Invalid dice format: expected a number, a dice or '('."))?
+        };
+        self.node(expr)
+    }
+
+    fn dice(&mut self, count: Option<u32>) -> Result<Expr> {
+        let sides = if self.eat("f") {
+            Sides::Fate
+        } else if self.eat("%") {
+            Sides::Percentile
+        } else {
+            self.number()?
+                .map(Sides::Numeric)
+                .ok_or_else(|| anyhow!("Invalid dice format: missing number of sides."))?
+        };
+        let mut modifiers = TermModifiers::default();
+        loop {
+            if let Some(selector) = self.selector()? {
+                Self::set_once(&mut modifiers.selector, selector, "selector")?;
+            } else if let Some(explosion) = self.explosion()? {
+                Self::set_once(&mut modifiers.explosion, explosion, "explosion")?;
+            } else if let Some(reroll) = self.reroll()? {
+                Self::set_once(&mut modifiers.reroll, reroll, "reroll")?;
+            } else if let Some(target) = self.target()? {
+                Self::set_once(&mut modifiers.target, target, "target")?;
+            } else if self.eat("cs") {
+                let critical = self.critical_range()?;
+                Self::set_once(&mut modifiers.critical, critical, "critical hit")?;
+            } else if self.eat("cf") {
+                let fumble = self.critical_range()?;
+                Self::set_once(&mut modifiers.fumble, fumble, "fumble")?;
+            } else {
+                break;
+            }
+        }
+        Ok(Expr::Roll(DiceTerm::new(
+            count.unwrap_or(1),
+            sides,
+            modifiers,
+        )?))
+    }
+
+    fn set_once<T>(slot: &mut Option<T>, value: T, name: &str) -> Result<()> {
+        if slot.replace(value).is_some() {
+            return Err(anyhow!("Invalid dice format: duplicate {} modifier.", name));
+        }
+        Ok(())
+    }
+
+    fn selector(&mut self) -> Result<Option<Selector>> {
+        let kind: fn(u32) -> Selector = if self.eat("kh") {
+            Selector::KeepHighest
+        } else if self.eat("kl") {
+            Selector::KeepLowest
+        } else if self.eat("k") {
+            Selector::KeepHighest
+        } else if self.eat("dh") {
+            Selector::DropHighest
+        } else if self.eat("dl") {
+            Selector::DropLowest
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(kind(self.number()?.unwrap_or(1))))
+    }
+
+    fn explosion(&mut self) -> Result<Option<Explosion>> {
+        let compound = if self.eat("!!") {
+            true
+        } else if self.eat("!") {
+            false
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(Explosion {
+            compound,
+            on: self.compare_point()?,
+        }))
+    }
+
+    fn reroll(&mut self) -> Result<Option<Reroll>> {
+        let once = if self.eat("ro") {
+            true
+        } else if self.eat("r") {
+            false
+        } else {
+            return Ok(None);
+        };
+        let on = self
+            .compare_point()?
+            .ok_or_else(|| anyhow!("Invalid dice format: missing reroll condition."))?;
+        Ok(Some(Reroll { once, on }))
+    }
+
+    fn target(&mut self) -> Result<Option<Target>> {
+        let Some(op) = self.compare_op() else {
+            return Ok(None);
+        };
+        let value = self
+            .number()?
+            .ok_or_else(|| anyhow!("Invalid dice format: missing number in a comparison."))?;
+        let failure = if self.eat("f") {
+            let failure = self
+                .compare_point()?
+                .ok_or_else(|| anyhow!("Invalid dice format: missing failure condition."))?;
+            Some(failure)
+        } else {
+            None
+        };
+        Ok(Some(Target {
+            success: ComparePoint::new(op, value),
+            failure,
+        }))
+    }
+
+    fn critical_range(&mut self) -> Result<ComparePoint> {
+        self.compare_point()?
+            .ok_or_else(|| anyhow!("Invalid dice format: missing critical range."))
+    }
+
+    fn compare_point(&mut self) -> Result<Option<ComparePoint>> {
+        let op = self.compare_op();
+        match (op, self.number()?) {
+            (op, Some(value)) => Ok(Some(ComparePoint::new(op.unwrap_or(CompareOp::Eq), value))),
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(anyhow!(
+                "Invalid dice format: missing number in a comparison."
+            )),
+        }
+    }
+
+    fn compare_op(&mut self) -> Option<CompareOp> {
+        if self.eat("<=") {
+            Some(CompareOp::Le)
+        } else if self.eat(">=") {
+            Some(CompareOp::Ge)
+        } else if self.eat("<") {
+            Some(CompareOp::Lt)
+        } else if self.eat(">") {
+            Some(CompareOp::Gt)
+        } else if self.eat("=") {
+            Some(CompareOp::Eq)
+        } else {
+            None
+        }
+    }
+
+    /// Counts a node of the syntax tree, refusing pathologically long expressions
+    fn node(&mut self, expr: Expr) -> Result<Expr> {
+        self.nodes += 1;
+        if self.nodes > MAX_NODES {
+            return Err(anyhow!("Dice expression is too long."));
+        }
+        Ok(expr)
+    }
+
+    /// Runs a sub-parser one group level deeper, refusing pathologically nested input
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Expr>) -> Result<Expr> {
+        if self.depth >= MAX_DEPTH {
+            return Err(anyhow!("Dice expression is nested too deeply."));
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn number(&mut self) -> Result<Option<u32>> {
+        let digits = self
+            .rest()
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.rest().len());
+        if digits == 0 {
+            return Ok(None);
+        }
+        let number = self.rest()[..digits]
+            .parse()
+            .map_err(|_| anyhow!("Number in dice expression is too large."))?;
+        self.pos += digits;
+        Ok(Some(number))
+    }
+
+    /// Consumes the given (case insensitive) literal if the input continues with it
+    fn eat(&mut self, literal: &str) -> bool {
+        let matches = self
+            .rest()
+            .get(..literal.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(literal));
+        if matches {
+            self.pos += literal.len();
+        }
+        matches
+    }
+
+    fn eat_token(&mut self, literal: &str) -> bool {
+        self.skip_whitespace();
+        self.eat(literal)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+}
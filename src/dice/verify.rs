@@ -0,0 +1,268 @@
+use anyhow::{Context, Result};
+use iroh::EndpointId;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+use crate::dice::{Dice, TermRoll};
+
+/// 32 bytes of randomness contributed to a roll
+pub type Seed = [u8; 32];
+
+/// Identifies a roll of a single peer between the commitment and the reveal
+pub type RollId = u64;
+
+/// How much a received roll can be trusted after recomputing it from the revealed seeds
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Verification {
+    /// The roll matches the roller's commitment and the seeds every peer sent. The seeds are not
+    /// committed to, so a roller controlling another peer could still pick its seed last.
+    Verified,
+    Unverified(String),
+    Forged(String),
+}
+
+/// Commits the roller to a secret and the dice before anyone contributes their seeds
+pub fn commitment(roll_id: RollId, secret: &Seed, dice: &Dice) -> Result<[u8; 32]> {
+    let dice_bytes =
+        postcard::to_stdvec(dice).context("Unexpected error serializing a dice expression.")?;
+
+    let mut hasher = blake3::Hasher::new_derive_key("cantrip dice roll commitment v1");
+    hasher.update(&roll_id.to_le_bytes());
+    hasher.update(secret);
+    hasher.update(&dice_bytes);
+    Ok(*hasher.finalize().as_bytes())
+}
+
+/// Combines the roller's secret with the seeds contributed by the other peers.
+///
+/// The roller cannot predict the result before the other peers reveal their seeds, and the
+/// peers cannot bias it without knowing the secret hidden behind the commitment.
+pub fn derive_seed(
+    roller: &EndpointId,
+    roll_id: RollId,
+    secret: &Seed,
+    seeds: &[(EndpointId, Seed)],
+) -> Seed {
+    let mut seeds = seeds.to_vec();
+    seeds.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+
+    let mut hasher = blake3::Hasher::new_derive_key("cantrip dice roll seed v1");
+    hasher.update(roller.as_bytes());
+    hasher.update(&roll_id.to_le_bytes());
+    hasher.update(secret);
+    for (contributor, seed) in &seeds {
+        hasher.update(contributor.as_bytes());
+        hasher.update(seed);
+    }
+    *hasher.finalize().as_bytes()
+}
+
+/// Everything a peer remembers about a commitment it has contributed a seed to
+pub struct Commitment {
+    pub commitment: [u8; 32],
+    pub own_seed: Seed,
+    /// The seeds the other peers contributed, as we saw them
+    pub seeds: Vec<(EndpointId, Seed)>,
+    pub received: Instant,
+}
+
+impl Commitment {
+    pub fn new(commitment: [u8; 32], own_seed: Seed) -> Self {
+        Self {
+            commitment,
+            own_seed,
+            seeds: Vec::new(),
+            received: Instant::now(),
+        }
+    }
+
+    /// Remembers the seed a peer contributed, keeping the first one they sent
+    pub fn add_seed(&mut self, contributor: EndpointId, seed: Seed) {
+        if self.seeds.iter().all(|(other, _)| *other != contributor) {
+            self.seeds.push((contributor, seed));
+        }
+    }
+}
+
+/// The revealed roll as received from the roller
+pub struct Reveal<'a> {
+    pub roller: &'a EndpointId,
+    pub roll_id: RollId,
+    pub dice: &'a Dice,
+    pub result: i64,
+    pub terms: &'a [TermRoll],
+    pub secret: &'a Seed,
+    pub seeds: &'a [(EndpointId, Seed)],
+}
+
+impl Reveal<'_> {
+    /// Checks the reveal against the commitment and recomputes the roll from the seeds
+    pub fn verify(&self, own_id: &EndpointId, commitment: Option<&Commitment>) -> Verification {
+        let Some(commitment) = commitment else {
+            return Verification::Unverified("no commitment was received".to_owned());
+        };
+
+        match self::commitment(self.roll_id, self.secret, self.dice) {
+            Ok(expected) if expected == commitment.commitment => {}
+            _ => {
+                return Verification::Forged("the reveal does not match the commitment".to_owned());
+            }
+        }
+
+        match self
+            .seeds
+            .iter()
+            .find(|(contributor, _)| contributor == own_id)
+        {
+            Some((_, seed)) if *seed != commitment.own_seed => {
+                return Verification::Forged("your seed was tampered with".to_owned());
+            }
+            None => return Verification::Unverified("your seed was not used".to_owned()),
+            Some(_) => {}
+        }
+
+        // the roller must not leave out or replace seeds to pick among the results
+        for (contributor, seed) in &commitment.seeds {
+            match self.seeds.iter().find(|(other, _)| other == contributor) {
+                Some((_, revealed)) if revealed == seed => {}
+                Some(_) => {
+                    return Verification::Forged(format!(
+                        "the seed of {} was tampered with",
+                        contributor.fmt_short()
+                    ));
+                }
+                None => {
+                    return Verification::Forged(format!(
+                        "the seed of {} was left out",
+                        contributor.fmt_short()
+                    ));
+                }
+            }
+        }
+        if let Some((contributor, _)) = self.seeds.iter().find(|(contributor, _)| {
+            contributor != own_id && commitment.seeds.iter().all(|(seen, _)| seen != contributor)
+        }) {
+            return Verification::Unverified(format!(
+                "the seed of {} was never seen",
+                contributor.fmt_short()
+            ));
+        }
+
+        let seed = derive_seed(self.roller, self.roll_id, self.secret, self.seeds);
+        let roll = self.dice.roll_seeded(seed);
+        if roll.total != self.result || roll.terms != self.terms {
+            return Verification::Forged("the rolls do not match the seeds".to_owned());
+        }
+        Verification::Verified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_id(byte: u8) -> EndpointId {
+        iroh::SecretKey::from_bytes(&[byte; 32]).public()
+    }
+
+    #[test]
+    fn test_verify_reveal() {
+        let roller = test_id(1);
+        let own_id = test_id(2);
+        let dice: Dice = "4d6kh3+2".parse().unwrap();
+        let secret = [7u8; 32];
+        let own_seed = [9u8; 32];
+        let seeds = vec![(own_id, own_seed), (test_id(3), [3u8; 32])];
+
+        let mut commitment =
+            Commitment::new(super::commitment(5, &secret, &dice).unwrap(), own_seed);
+        commitment.add_seed(test_id(3), [3u8; 32]);
+        let roll = dice.roll_seeded(derive_seed(&roller, 5, &secret, &seeds));
+        let reveal = Reveal {
+            roller: &roller,
+            roll_id: 5,
+            dice: &dice,
+            result: roll.total,
+            terms: &roll.terms,
+            secret: &secret,
+            seeds: &seeds,
+        };
+        assert_eq!(
+            reveal.verify(&own_id, Some(&commitment)),
+            Verification::Verified
+        );
+        assert!(matches!(
+            reveal.verify(&own_id, None),
+            Verification::Unverified(_)
+        ));
+
+        let forged = Reveal {
+            result: roll.total + 1,
+            ..reveal
+        };
+        assert!(matches!(
+            forged.verify(&own_id, Some(&commitment)),
+            Verification::Forged(_)
+        ));
+
+        let other_dice: Dice = "4d6+2".parse().unwrap();
+        let swapped = Reveal {
+            dice: &other_dice,
+            ..reveal
+        };
+        assert!(matches!(
+            swapped.verify(&own_id, Some(&commitment)),
+            Verification::Forged(_)
+        ));
+
+        let omitted = Reveal {
+            seeds: &seeds[1..],
+            ..reveal
+        };
+        assert!(matches!(
+            omitted.verify(&own_id, Some(&commitment)),
+            Verification::Unverified(_)
+        ));
+
+        // leaving out or replacing the seed of another peer is caught by everyone who saw it
+        let others_omitted = Reveal {
+            seeds: &seeds[..1],
+            ..reveal
+        };
+        assert!(matches!(
+            others_omitted.verify(&own_id, Some(&commitment)),
+            Verification::Forged(_)
+        ));
+        let replaced = [(own_id, own_seed), (test_id(3), [4u8; 32])];
+        let others_replaced = Reveal {
+            seeds: &replaced,
+            ..reveal
+        };
+        assert!(matches!(
+            others_replaced.verify(&own_id, Some(&commitment)),
+            Verification::Forged(_)
+        ));
+
+        let unseen = Commitment::new(commitment.commitment, own_seed);
+        assert!(matches!(
+            reveal.verify(&own_id, Some(&unseen)),
+            Verification::Unverified(_)
+        ));
+    }
+
+    #[test]
+    fn test_derive_seed_order_independent() {
+        let roller = test_id(1);
+        let seeds = [(test_id(2), [2u8; 32]), (test_id(3), [3u8; 32])];
+        let reversed = [seeds[1], seeds[0]];
+
+        assert_eq!(
+            derive_seed(&roller, 1, &[0u8; 32], &seeds),
+            derive_seed(&roller, 1, &[0u8; 32], &reversed)
+        );
+        assert_ne!(
+            derive_seed(&roller, 1, &[0u8; 32], &seeds),
+            derive_seed(&roller, 2, &[0u8; 32], &seeds)
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use crate::dice::Dice;
+use iroh::EndpointId;
+
+use crate::dice::{Dice, Odds, RollId, RollVisibility, Seed, TermRoll, Verification};
+use crate::message::MessageHeader;
+use crate::snapshot::Snapshot;
+use crate::status::StatusChange;
+use crate::tables::TableStep;
 
 pub enum SystemEvent {
     Ui(ChatEvent),
@@ -7,26 +13,138 @@ pub enum SystemEvent {
 
 pub enum NetworkEvent {
     BroadcastJoin(String),
+    ContributeSeed {
+        roller: EndpointId,
+        roll_id: RollId,
+        seed: Seed,
+    },
+    SeedReceived {
+        from: EndpointId,
+        roll_id: RollId,
+        seed: Seed,
+        expected: usize,
+    },
+    RevealRoll(RollId),
+    /// A player sent us their initiative, only when we are the DM
+    InitiativeReceived {
+        from: EndpointId,
+        name: String,
+        score: i64,
+    },
+    /// A player sent us a change to a combatant, only when we are the DM
+    StatusChangeReceived {
+        from: EndpointId,
+        name: String,
+        change: StatusChange,
+    },
+    /// A player asked us to pass their turn on, only when we are the DM
+    TurnEnded(EndpointId),
+    /// A peer joining late asked us for the recent chat messages
+    HistoryRequested(EndpointId),
+    /// Sends the game state again, for peers joining, only when we are the DM
+    ShareSnapshot,
+    /// The seed timeout of all rolls of an inline roll message has passed
+    RevealMessage(u64),
 }
 
 pub enum ChatEvent {
+    /// The event of a message received from the room, with the header ordering it
+    Received {
+        header: MessageHeader,
+        event: Box<ChatEvent>,
+    },
     MessageReceived {
         author: String,
         content: String,
     },
     MessageSent(String),
-    PeerJoined(String),
-    PeerLeft(String),
+    Emote {
+        author: String,
+        text: String,
+    },
+    /// A private message between us and the peer, sent by us unless `incoming`
+    Whisper {
+        peer: String,
+        text: String,
+        incoming: bool,
+    },
+    /// Out-of-character text, `author` is None for our own
+    Ooc {
+        author: Option<String>,
+        text: String,
+    },
+    PeerJoined {
+        id: EndpointId,
+        name: String,
+    },
+    PeerLeft {
+        id: EndpointId,
+        name: String,
+    },
+    /// A newer game state from the DM
+    SnapshotReceived {
+        snapshot: Snapshot,
+        notes: Vec<String>,
+    },
+    /// A peer removed from the table by the DM
+    PeerKicked {
+        id: EndpointId,
+        name: String,
+    },
     PeerNameChange {
+        id: EndpointId,
         old: String,
         new: String,
     },
     SystemStatus(String),
     DiceRolled {
-        result: u32,
-        rolls: Vec<u32>,
+        roller: EndpointId,
+        result: i64,
+        terms: Vec<TermRoll>,
         dice: Dice,
         author: Option<String>,
+        verification: Verification,
+        visibility: RollVisibility,
+    },
+    /// A roll whose result is hidden from us, `dice` are only known for our own blind rolls
+    HiddenRoll {
+        author: Option<String>,
+        dice: Option<Dice>,
+        visibility: RollVisibility,
+    },
+    /// A chat message with rolls embedded in the text
+    InlineRolled {
+        roller: EndpointId,
+        author: Option<String>,
+        parts: Vec<InlineSegment>,
+    },
+    /// A roll on a random table, `steps` holding the rolls on every table followed.
+    /// The verification covers the first roll, the nested tables are not part of the commitment.
+    TableRolled {
+        roller: EndpointId,
+        author: Option<String>,
+        entry: String,
+        steps: Vec<TableStep>,
+        verification: Verification,
     },
+    /// Odds of an expression computed locally, optionally against a target number
+    Odds {
+        dice: Dice,
+        odds: Odds,
+        target: Option<i64>,
+    },
+    /// Usage and description of commands, listed by `/help`
+    Help(Vec<(String, String)>),
     Error(String),
 }
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InlineSegment {
+    Text(String),
+    Roll {
+        result: i64,
+        dice: Dice,
+        terms: Vec<TermRoll>,
+        verification: Verification,
+    },
+}
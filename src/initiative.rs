@@ -0,0 +1,175 @@
+use anyhow::{Result, anyhow};
+use iroh::EndpointId;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+/// A combatant in the turn order, either a player or an NPC added by the DM
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InitiativeEntry {
+    pub name: String,
+    pub score: i64,
+    /// The peer playing the combatant, None for NPCs
+    pub player: Option<EndpointId>,
+}
+
+impl InitiativeEntry {
+    /// Whether both entries are the same combatant, players by their identity and NPCs by name
+    fn is_same(&self, other: &InitiativeEntry) -> bool {
+        match (self.player, other.player) {
+            (Some(player), Some(other)) => player == other,
+            (None, None) => self.name.eq_ignore_ascii_case(&other.name),
+            _ => false,
+        }
+    }
+}
+
+/// The turn order of a combat, kept by the DM and broadcast to the others on every change
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Initiative {
+    /// Sorted from the highest score, ties in the order the combatants were added
+    entries: Vec<InitiativeEntry>,
+    /// The entry whose turn it is, None before the first `next`
+    turn: Option<usize>,
+    round: u32,
+}
+
+impl Initiative {
+    pub fn entries(&self) -> &[InitiativeEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn turn(&self) -> Option<usize> {
+        self.turn
+    }
+
+    pub fn current(&self) -> Option<&InitiativeEntry> {
+        self.entries.get(self.turn?)
+    }
+
+    /// Sets the initiative of a player, replacing their previous one
+    pub fn set_player(&mut self, player: EndpointId, name: String, score: i64) {
+        self.set(InitiativeEntry {
+            name,
+            score,
+            player: Some(player),
+        });
+    }
+
+    /// Adds an NPC, replacing the one of the same name
+    pub fn set_npc(&mut self, name: String, score: i64) {
+        self.set(InitiativeEntry {
+            name,
+            score,
+            player: None,
+        });
+    }
+
+    /// Removes the combatant of the name, passing the turn on if it was theirs
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.entries.len();
+        self.update(|entries| entries.retain(|entry| !entry.name.eq_ignore_ascii_case(name)));
+        self.entries.len() != before
+    }
+
+    /// Passes the turn to the next combatant, starting a new round after the last one
+    pub fn next(&mut self) -> Result<&InitiativeEntry> {
+        if self.entries.is_empty() {
+            return Err(anyhow!("Nobody has rolled initiative yet."));
+        }
+        let turn = match self.turn {
+            None => {
+                self.round = 1;
+                0
+            }
+            Some(turn) if turn + 1 >= self.entries.len() => {
+                self.round += 1;
+                0
+            }
+            Some(turn) => turn + 1,
+        };
+        self.turn = Some(turn);
+        Ok(&self.entries[turn])
+    }
+
+    /// Ends the combat
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn set(&mut self, entry: InitiativeEntry) {
+        self.update(|entries| {
+            entries.retain(|other| !other.is_same(&entry));
+            entries.push(entry);
+        });
+    }
+
+    /// Changes the entries and sorts them again, keeping the turn with the same combatant
+    fn update(&mut self, change: impl FnOnce(&mut Vec<InitiativeEntry>)) {
+        let current = self.current().cloned();
+        change(&mut self.entries);
+        self.entries.sort_by_key(|entry| Reverse(entry.score));
+
+        self.turn = match (current, self.turn) {
+            (_, None) => None,
+            _ if self.entries.is_empty() => None,
+            (Some(current), Some(turn)) => Some(
+                self.entries
+                    .iter()
+                    .position(|entry| entry.is_same(&current))
+                    .unwrap_or(turn % self.entries.len()),
+            ),
+            (None, Some(turn)) => Some(turn % self.entries.len()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(initiative: &Initiative) -> Vec<&str> {
+        initiative
+            .entries()
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_turn_order() {
+        let alice = iroh::SecretKey::from_bytes(&[1u8; 32]).public();
+        let mut initiative = Initiative::default();
+        assert!(initiative.next().is_err());
+
+        initiative.set_player(alice, "Alice".to_owned(), 12);
+        initiative.set_npc("Goblin".to_owned(), 15);
+        initiative.set_npc("Orc".to_owned(), 12);
+        assert_eq!(names(&initiative), ["Goblin", "Alice", "Orc"]);
+
+        assert_eq!(initiative.next().unwrap().name, "Goblin");
+        assert_eq!(initiative.round(), 1);
+        assert_eq!(initiative.next().unwrap().name, "Alice");
+
+        // the turn stays with Alice when the order changes around her
+        initiative.set_player(alice, "Alice".to_owned(), 18);
+        initiative.set_npc("Wolf".to_owned(), 20);
+        assert_eq!(names(&initiative), ["Wolf", "Alice", "Goblin", "Orc"]);
+        assert_eq!(initiative.current().unwrap().name, "Alice");
+
+        // removing her passes the turn to the one after her
+        assert!(initiative.remove("alice"));
+        assert_eq!(initiative.current().unwrap().name, "Goblin");
+
+        assert_eq!(initiative.next().unwrap().name, "Orc");
+        assert_eq!(initiative.next().unwrap().name, "Wolf");
+        assert_eq!(initiative.round(), 2);
+    }
+}
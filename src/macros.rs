@@ -0,0 +1,105 @@
+use anyhow::{Result, anyhow};
+use std::collections::BTreeMap;
+
+use crate::dice::Dice;
+use crate::secrets::{load_macros, save_macros};
+
+/// Named roll shortcuts of a user, such as `longsword` expanding to `1d20+7 ; 1d8+4`
+pub struct Macros {
+    username: String,
+    macros: BTreeMap<String, String>,
+}
+
+impl Macros {
+    /// Loads the macros stored for the username, starting with none if there are no stored ones
+    pub fn load(username: &str) -> Self {
+        Self {
+            username: username.to_owned(),
+            macros: load_macros(username).unwrap_or_default(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.macros.get(name).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.macros
+            .iter()
+            .map(|(name, expansion)| (name.as_str(), expansion.as_str()))
+    }
+
+    /// Adds or replaces a macro, checking that every roll of the expansion is valid
+    pub fn insert(&mut self, name: &str, expansion: &str) -> Result<()> {
+        validate_name(name)?;
+        let rolls = parse_expansion(expansion)?;
+        let expansion = rolls
+            .iter()
+            .map(Dice::to_string)
+            .collect::<Vec<_>>()
+            .join(" ; ");
+        self.macros.insert(name.to_owned(), expansion);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.macros.remove(name).is_some()
+    }
+
+    /// Stores the macros in the keychain next to the identity of the user
+    pub fn save(&self) -> Result<()> {
+        save_macros(&self.username, &self.macros)
+    }
+}
+
+/// Splits a macro expansion into the rolls separated by `;`
+pub fn parse_expansion(expansion: &str) -> Result<Vec<Dice>> {
+    expansion
+        .split(';')
+        .map(|roll| match roll.trim() {
+            "" => Err(anyhow!("Invalid macro: empty roll.")),
+            roll => roll.parse(),
+        })
+        .collect()
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if name.is_empty() || !valid {
+        return Err(anyhow!(
+            "Invalid macro name: use only letters, digits, '-' and '_'."
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_macros() -> Macros {
+        Macros {
+            username: "test".to_owned(),
+            macros: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_insert_macro() {
+        let mut macros = test_macros();
+        macros.insert("longsword", "1d20+7 ;1d8 + 4").unwrap();
+        assert_eq!(macros.get("longsword"), Some("1d20+7 ; 1d8+4"));
+        assert_eq!(parse_expansion("1d20+7 ; 1d8+4").unwrap().len(), 2);
+
+        assert!(macros.insert("long sword", "1d20").is_err());
+        assert!(macros.insert("", "1d20").is_err());
+        assert!(macros.insert("bad", "1d20 ; ").is_err());
+        assert!(macros.insert("bad", "1d20 ; 1d").is_err());
+        assert_eq!(macros.get("bad"), None);
+
+        assert!(macros.remove("longsword"));
+        assert!(!macros.remove("longsword"));
+    }
+}
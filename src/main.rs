@@ -3,13 +3,19 @@ mod cli;
 mod command;
 mod dice;
 mod events;
+mod initiative;
+mod macros;
 mod message;
 mod secrets;
+mod snapshot;
+mod status;
+mod tables;
 mod ticket;
 mod ui;
 
 use crate::chat::{ChatApp, ChatClient, ChatConfig, ChatRoom};
 use crate::cli::Cli;
+use crate::macros::Macros;
 use crate::ui::{InputSource, UserInterface, tui::TerminalInterface};
 use anyhow::Result;
 use tokio::sync::{broadcast, mpsc};
@@ -42,7 +48,15 @@ async fn main() -> Result<()> {
     let (event_tx, mut event_rx) = mpsc::channel(100);
     let (shutdown_tx, _) = broadcast::channel(1);
 
-    let mut app = ChatApp::new(renderer, shutdown_tx.clone());
+    let macros = Macros::load(&chat_config.username);
+    let mut app = ChatApp::new(
+        renderer,
+        shutdown_tx.clone(),
+        event_tx.downgrade(),
+        chat_config.username.clone(),
+        macros,
+        chat_config.tables.clone(),
+    );
     let mut connect_task = Box::pin(ChatRoom::connect(chat_config, event_tx));
     let mut backend_handle: Option<JoinHandle<()>> = None;
 
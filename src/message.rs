@@ -3,10 +3,23 @@ use chacha20poly1305::{
     AeadCore, KeyInit, XChaCha20Poly1305, XNonce,
     aead::{Aead, OsRng},
 };
-use iroh::EndpointId;
+use iroh::{EndpointId, SecretKey, Signature};
+use iroh_gossip::TopicId;
 use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::dice::Dice;
+use crate::chat::BacklogEntry;
+use crate::dice::{Dice, RollId, RollVisibility, Seed, TermRoll};
+use crate::secrets::shared_secret;
+use crate::snapshot::Snapshot;
+use crate::status::StatusChange;
+use crate::tables::TableStep;
+
+/// The gossip message size of the room, larger than the default to fit history responses
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+const DM_CONTEXT: &[u8] = b"cantrip dm action v2";
+const PLAYER_CONTEXT: &[u8] = b"cantrip player action v2";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Message {
@@ -14,6 +27,48 @@ pub struct Message {
     nonce: [u8; 24],
 }
 
+/// Identifies a message of the room and orders it the same way on every peer: by the sender's
+/// Lamport clock, ties broken by the random ID
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageHeader {
+    pub lamport: u64,
+    pub id: [u8; 16],
+    /// The sender's wall-clock time, in milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// The header and body encrypted together into a gossip message
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    header: MessageHeader,
+    body: MessageBody,
+}
+
+/// A message only a single recipient can decrypt, using a key exchanged between a throwaway
+/// ephemeral identity and the recipient's identity
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SealedMessage {
+    ephemeral: EndpointId,
+    message: Message,
+}
+
+/// An operation only the DM may perform
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum DmAction {
+    /// Removes the peer from the table
+    Kick { target: EndpointId },
+    /// The game state, with what the table should be told about the change leading to it
+    Snapshot {
+        snapshot: Snapshot,
+        notes: Vec<String>,
+    },
+    /// The recent chat messages, for the peer who asked for them
+    History {
+        to: EndpointId,
+        entries: Vec<BacklogEntry>,
+    },
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum MessageBody {
     Joined {
@@ -27,33 +82,211 @@ pub enum MessageBody {
         from: EndpointId,
         text: String,
     },
+    /// An action of the sender's character, shown as "*name text*"
+    Emote {
+        from: EndpointId,
+        text: String,
+    },
+    /// Out-of-character table talk
+    Ooc {
+        from: EndpointId,
+        text: String,
+    },
+    /// A `Text` body only the sender and the recipient can decrypt, see [`Message::whisper`]
+    Whisper {
+        from: EndpointId,
+        to: EndpointId,
+        message: Message,
+    },
+    /// Announces a roll by committing to a secret seed and the dice, before anyone contributes
+    RollCommit {
+        from: EndpointId,
+        roll_id: RollId,
+        commitment: [u8; 32],
+    },
+    /// A seed contributed by a peer to the roll committed by `roller`
+    RollSeed {
+        from: EndpointId,
+        roller: EndpointId,
+        roll_id: RollId,
+        seed: Seed,
+    },
+    /// Reveals the committed roll together with everything needed to recompute it
     DiceRoll {
         from: EndpointId,
-        result: u32,
+        roll_id: RollId,
+        result: i64,
         dice: Dice,
-        rolls: Vec<u32>,
+        terms: Vec<TermRoll>,
+        secret: Seed,
+        seeds: Vec<(EndpointId, Seed)>,
+    },
+    /// Reveals a committed roll only to the DM, sealing the `DiceRoll` body to their identity
+    HiddenRoll {
+        from: EndpointId,
+        roll_id: RollId,
+        visibility: RollVisibility,
+        sealed: SealedMessage,
+    },
+    /// A chat message with rolls embedded in the text, each revealed like a `DiceRoll`
+    InlineRolls {
+        from: EndpointId,
+        parts: Vec<InlinePart>,
     },
+    /// Shows the others what a roll macro of the sender expands to
+    MacroShared {
+        from: EndpointId,
+        name: String,
+        expansion: String,
+    },
+    /// Asks the DM for the chat messages sent before we joined
+    HistoryRequest {
+        from: EndpointId,
+    },
+    /// An action of a player for the DM, signed with the player's identity so that nobody can act
+    /// for another player
+    Player {
+        from: EndpointId,
+        action: PlayerAction,
+        timestamp: u64,
+        signature: Signature,
+    },
+    /// A DM action signed with the DM's identity, as the room key alone lets anyone in the
+    /// room write any `from`
+    Dm {
+        from: EndpointId,
+        action: DmAction,
+        timestamp: u64,
+        signature: Signature,
+    },
+    /// Reveals a committed roll on a random table with the entry it picked, the first step
+    /// being the committed roll and the rest the rolls on the nested tables
+    TableRoll {
+        from: EndpointId,
+        roll_id: RollId,
+        entry: String,
+        steps: Vec<TableStep>,
+        secret: Seed,
+        seeds: Vec<(EndpointId, Seed)>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum InlinePart {
+    Text(String),
+    Roll {
+        roll_id: RollId,
+        result: i64,
+        dice: Dice,
+        terms: Vec<TermRoll>,
+        secret: Seed,
+        seeds: Vec<(EndpointId, Seed)>,
+    },
+}
+
+impl MessageHeader {
+    /// A header with a fresh ID and the current time
+    pub fn new(lamport: u64) -> Self {
+        Self {
+            lamport,
+            id: rand::random(),
+            timestamp: now_millis(),
+        }
+    }
+}
+
+/// The wall-clock time in milliseconds since the Unix epoch
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// The bytes signed for an action, bound to the room and the time it was made so that it cannot
+/// be replayed in another room or later in the same one
+fn signed_bytes<T: Serialize>(
+    context: &[u8],
+    topic: &TopicId,
+    timestamp: u64,
+    action: &T,
+) -> Vec<u8> {
+    let mut bytes = context.to_vec();
+    bytes.extend(topic.as_bytes());
+    bytes.extend(timestamp.to_le_bytes());
+    bytes.extend(postcard::to_stdvec(action).expect("Unexpected error serializing an action"));
+    bytes
 }
 
 impl Message {
     /// Creates a new message from the message body by encrypting it with the key and a random
     /// generated nonce
     pub fn new(body: MessageBody, key: &[u8; 32]) -> Result<Self> {
-        let nonce: [u8; 24] = XChaCha20Poly1305::generate_nonce(&mut OsRng).into();
-        let ciphertext = body.encrypt(&nonce, key)?;
-        Ok(Self { ciphertext, nonce })
+        Self::encrypt(&body.to_vec()?, key)
     }
 
     /// Tries to decrypt a message body using the saved nonce and a specified key
     pub fn decrypt(&self, key: &[u8; 32]) -> Result<MessageBody> {
+        MessageBody::from_bytes(&self.decrypt_bytes(key)?)
+    }
+
+    /// Creates a message sent to the room, encrypting the header together with the body
+    pub fn with_header(header: MessageHeader, body: MessageBody, key: &[u8; 32]) -> Result<Self> {
+        let envelope = postcard::to_stdvec(&Envelope { header, body })
+            .context("Unexpected error serializing a message body.")?;
+        Self::encrypt(&envelope, key)
+    }
+
+    /// Decrypts a message sent to the room into its header and body
+    pub fn open_envelope(&self, key: &[u8; 32]) -> Result<(MessageHeader, MessageBody)> {
+        let Envelope { header, body } = postcard::from_bytes(&self.decrypt_bytes(key)?)?;
+        Ok((header, body))
+    }
+
+    fn encrypt(plaintext: &[u8], key: &[u8; 32]) -> Result<Self> {
+        let nonce: [u8; 24] = XChaCha20Poly1305::generate_nonce(&mut OsRng).into();
+        let cipher = XChaCha20Poly1305::new(key.into());
+        let ciphertext = cipher
+            .encrypt(&nonce.into(), plaintext)
+            .map_err(|_| anyhow!("Unexpected failure while encrypting a message."))?;
+        Ok(Self { ciphertext, nonce })
+    }
+
+    fn decrypt_bytes(&self, key: &[u8; 32]) -> Result<Vec<u8>> {
         let cipher = XChaCha20Poly1305::new(key.into());
         let nonce = XNonce::from_slice(&self.nonce);
 
-        let decrypt = cipher
+        cipher
             .decrypt(nonce, &self.ciphertext[..])
-            .map_err(|_| anyhow!("Failed to decrypt a message"))?;
+            .map_err(|_| anyhow!("Failed to decrypt a message"))
+    }
 
-        MessageBody::from_bytes(&decrypt)
+    /// Encrypts the message body with a key derived from the identities of the sender and the
+    /// recipient, so that nobody else in the room can decrypt it
+    pub fn whisper(
+        body: MessageBody,
+        secret_key: &SecretKey,
+        recipient: &EndpointId,
+    ) -> Result<Self> {
+        Self::new(body, &Self::pairwise_key(secret_key, recipient)?)
+    }
+
+    /// Decrypts a message whispered between our identity and the peer's
+    pub fn open_whisper(&self, secret_key: &SecretKey, peer: &EndpointId) -> Result<MessageBody> {
+        self.decrypt(&Self::pairwise_key(secret_key, peer)?)
+    }
+
+    fn pairwise_key(secret_key: &SecretKey, peer: &EndpointId) -> Result<[u8; 32]> {
+        let own = secret_key.public();
+        let (first, second) = if own.as_bytes() < peer.as_bytes() {
+            (own, *peer)
+        } else {
+            (*peer, own)
+        };
+        let mut hasher = blake3::Hasher::new_derive_key("cantrip whisper v1");
+        hasher.update(&shared_secret(secret_key, peer)?);
+        hasher.update(first.as_bytes());
+        hasher.update(second.as_bytes());
+        Ok(*hasher.finalize().as_bytes())
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
@@ -65,18 +298,146 @@ impl Message {
     }
 }
 
+impl SealedMessage {
+    /// Encrypts the message body so that only the recipient is able to decrypt it
+    pub fn seal(body: &MessageBody, recipient: &EndpointId) -> Result<Self> {
+        let ephemeral_key = SecretKey::generate(&mut rand::rng());
+        let ephemeral = ephemeral_key.public();
+        let key = Self::derive_key(&ephemeral_key, recipient, &ephemeral, recipient)?;
+        Ok(Self {
+            ephemeral,
+            message: Message::encrypt(&body.to_vec()?, &key)?,
+        })
+    }
+
+    /// Decrypts a message sealed to the identity of the secret key
+    pub fn open(&self, secret_key: &SecretKey) -> Result<MessageBody> {
+        let key = Self::derive_key(
+            secret_key,
+            &self.ephemeral,
+            &self.ephemeral,
+            &secret_key.public(),
+        )?;
+        self.message.decrypt(&key)
+    }
+
+    fn derive_key(
+        secret_key: &SecretKey,
+        peer: &EndpointId,
+        ephemeral: &EndpointId,
+        recipient: &EndpointId,
+    ) -> Result<[u8; 32]> {
+        let mut hasher = blake3::Hasher::new_derive_key("cantrip sealed message v1");
+        hasher.update(&shared_secret(secret_key, peer)?);
+        hasher.update(ephemeral.as_bytes());
+        hasher.update(recipient.as_bytes());
+        Ok(*hasher.finalize().as_bytes())
+    }
+}
+
+/// An operation of a player on their own combatant, for the DM to apply
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum PlayerAction {
+    /// The player's initiative for the DM to put in the turn order
+    Initiative { score: i64 },
+    /// Asks the DM to pass the turn on, sent by the combatant whose turn it is
+    EndTurn,
+    /// A change to the player's combatant for the DM to apply
+    ChangeStatus { change: StatusChange },
+}
+
+impl DmAction {
+    /// Signs the action with the DM's secret key, for the room at the time
+    pub fn sign(self, secret_key: &SecretKey, topic: &TopicId, timestamp: u64) -> MessageBody {
+        let signature = secret_key.sign(&signed_bytes(DM_CONTEXT, topic, timestamp, &self));
+        MessageBody::Dm {
+            from: secret_key.public(),
+            action: self,
+            timestamp,
+            signature,
+        }
+    }
+
+    /// Whether the action was signed by the DM for the room at the time
+    pub fn verify(
+        &self,
+        dm: &EndpointId,
+        topic: &TopicId,
+        timestamp: u64,
+        signature: &Signature,
+    ) -> bool {
+        dm.verify(&signed_bytes(DM_CONTEXT, topic, timestamp, self), signature)
+            .is_ok()
+    }
+}
+
+impl PlayerAction {
+    /// Signs the action with the player's secret key, for the room at the time
+    pub fn sign(self, secret_key: &SecretKey, topic: &TopicId, timestamp: u64) -> MessageBody {
+        let signature = secret_key.sign(&signed_bytes(PLAYER_CONTEXT, topic, timestamp, &self));
+        MessageBody::Player {
+            from: secret_key.public(),
+            action: self,
+            timestamp,
+            signature,
+        }
+    }
+
+    /// Whether the action was signed by the player for the room at the time
+    pub fn verify(
+        &self,
+        player: &EndpointId,
+        topic: &TopicId,
+        timestamp: u64,
+        signature: &Signature,
+    ) -> bool {
+        player
+            .verify(
+                &signed_bytes(PLAYER_CONTEXT, topic, timestamp, self),
+                signature,
+            )
+            .is_ok()
+    }
+}
+
 impl MessageBody {
-    fn to_vec(&self) -> Result<Vec<u8>> {
-        postcard::to_stdvec(self).context("Unexpected error serializing a message body.")
+    /// The peer the message claims to be from
+    pub fn author(&self) -> EndpointId {
+        match self {
+            Self::Joined { from, .. }
+            | Self::Left { from }
+            | Self::Text { from, .. }
+            | Self::Emote { from, .. }
+            | Self::Ooc { from, .. }
+            | Self::Whisper { from, .. }
+            | Self::RollCommit { from, .. }
+            | Self::RollSeed { from, .. }
+            | Self::DiceRoll { from, .. }
+            | Self::HiddenRoll { from, .. }
+            | Self::InlineRolls { from, .. }
+            | Self::MacroShared { from, .. }
+            | Self::Player { from, .. }
+            | Self::HistoryRequest { from }
+            | Self::Dm { from, .. }
+            | Self::TableRoll { from, .. } => *from,
+        }
     }
 
-    /// Encrypts the message body using the specified key and nonce
-    fn encrypt(&self, nonce: &[u8; 24], key: &[u8; 32]) -> Result<Vec<u8>> {
-        let cipher = XChaCha20Poly1305::new(key.into());
+    /// Whether the message is part of the conversation, kept for peers joining late
+    pub fn is_chat(&self) -> bool {
+        matches!(
+            self,
+            Self::Text { .. }
+                | Self::Emote { .. }
+                | Self::Ooc { .. }
+                | Self::DiceRoll { .. }
+                | Self::InlineRolls { .. }
+                | Self::TableRoll { .. }
+        )
+    }
 
-        cipher
-            .encrypt(nonce.into(), &self.to_vec()?[..])
-            .map_err(|_| anyhow!("Unexpected failure while encrypting a message."))
+    fn to_vec(&self) -> Result<Vec<u8>> {
+        postcard::to_stdvec(self).context("Unexpected error serializing a message body.")
     }
 
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
@@ -122,6 +483,126 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_envelope() {
+        let header = MessageHeader::new(7);
+        let body = MessageBody::Text {
+            from: test_id(),
+            text: test_text(),
+        };
+        let message = Message::with_header(header, body, &test_key()).unwrap();
+
+        let (opened, body) = message.open_envelope(&test_key()).unwrap();
+        assert_eq!(opened, header);
+        assert!(matches!(body, MessageBody::Text { text, .. } if text == test_text()));
+
+        // the Lamport clock orders before the wall clock of the sender
+        let later = MessageHeader {
+            timestamp: 0,
+            ..MessageHeader::new(8)
+        };
+        assert!(header < later);
+    }
+
+    #[test]
+    fn test_seal_open() {
+        let recipient = SecretKey::generate(&mut rand::rng());
+        let eavesdropper = SecretKey::generate(&mut rand::rng());
+        let body = MessageBody::Text {
+            from: test_id(),
+            text: test_text(),
+        };
+
+        let sealed = SealedMessage::seal(&body, &recipient.public()).unwrap();
+
+        match sealed.open(&recipient).unwrap() {
+            MessageBody::Text { from, text } => {
+                assert_eq!(from, test_id());
+                assert_eq!(text, test_text());
+            }
+            _ => panic!("Opening the sealed message went wrong"),
+        }
+        assert!(sealed.open(&eavesdropper).is_err());
+    }
+
+    #[test]
+    fn test_whisper() {
+        let sender = SecretKey::generate(&mut rand::rng());
+        let recipient = SecretKey::generate(&mut rand::rng());
+        let eavesdropper = SecretKey::generate(&mut rand::rng());
+        let body = MessageBody::Text {
+            from: sender.public(),
+            text: test_text(),
+        };
+
+        let message = Message::whisper(body, &sender, &recipient.public()).unwrap();
+
+        match message.open_whisper(&recipient, &sender.public()).unwrap() {
+            MessageBody::Text { text, .. } => assert_eq!(text, test_text()),
+            _ => panic!("Opening the whisper went wrong"),
+        }
+        assert!(message.open_whisper(&sender, &recipient.public()).is_ok());
+        assert!(
+            message
+                .open_whisper(&eavesdropper, &sender.public())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_dm_action_signature() {
+        let dm = SecretKey::generate(&mut rand::rng());
+        let player = SecretKey::generate(&mut rand::rng());
+        let topic = TopicId::from_bytes([1u8; 32]);
+        let action = DmAction::Kick { target: test_id() };
+
+        let MessageBody::Dm {
+            action,
+            timestamp,
+            signature,
+            ..
+        } = action.sign(&dm, &topic, 1000)
+        else {
+            panic!("Signing the action went wrong")
+        };
+        assert!(action.verify(&dm.public(), &topic, timestamp, &signature));
+        assert!(!action.verify(&player.public(), &topic, timestamp, &signature));
+
+        let forged = DmAction::Kick {
+            target: player.public(),
+        };
+        assert!(!forged.verify(&dm.public(), &topic, timestamp, &signature));
+
+        // replaying the action in another room or at another time fails
+        let other_topic = TopicId::from_bytes([2u8; 32]);
+        assert!(!action.verify(&dm.public(), &other_topic, timestamp, &signature));
+        assert!(!action.verify(&dm.public(), &topic, timestamp + 1, &signature));
+    }
+
+    #[test]
+    fn test_player_action_signature() {
+        let player = SecretKey::generate(&mut rand::rng());
+        let other = SecretKey::generate(&mut rand::rng());
+        let topic = TopicId::from_bytes([1u8; 32]);
+
+        let MessageBody::Player {
+            action,
+            timestamp,
+            signature,
+            ..
+        } = PlayerAction::Initiative { score: 12 }.sign(&player, &topic, 1000)
+        else {
+            panic!("Signing the action went wrong")
+        };
+        let verify = |action: &PlayerAction, player: &SecretKey| {
+            action.verify(&player.public(), &topic, timestamp, &signature)
+        };
+        assert!(verify(&action, &player));
+        assert!(!verify(&action, &other));
+        assert!(!verify(&PlayerAction::Initiative { score: 20 }, &player));
+        assert!(!verify(&PlayerAction::EndTurn, &player));
+    }
+
     #[test]
     fn test_decrypt_fail_wrong_key() {
         let wrong_key = test_key().map(|u| u.wrapping_add(1));
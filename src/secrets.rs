@@ -1,7 +1,8 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use argon2::Argon2;
-use iroh::SecretKey;
+use iroh::{EndpointId, SecretKey};
 use keyring::Entry;
+use std::collections::BTreeMap;
 
 pub fn get_secret_key(username: &str) -> Result<SecretKey> {
     let entry = match Entry::new("cantrip-rs", username) {
@@ -21,6 +22,25 @@ pub fn get_secret_key(username: &str) -> Result<SecretKey> {
     }
 }
 
+/// Loads the roll macros stored in the keychain next to the identity of the user
+pub fn load_macros(username: &str) -> Result<BTreeMap<String, String>> {
+    let entry = Entry::new("cantrip-rs-macros", username)?;
+    match entry.get_secret() {
+        Ok(bytes) => postcard::from_bytes(&bytes)
+            .context("Failed to read the macros stored in the keychain."),
+        Err(keyring::Error::NoEntry) => Ok(BTreeMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn save_macros(username: &str, macros: &BTreeMap<String, String>) -> Result<()> {
+    let entry = Entry::new("cantrip-rs-macros", username)?;
+    let bytes = postcard::to_stdvec(macros).context("Unexpected error serializing macros.")?;
+    entry
+        .set_secret(&bytes)
+        .context("Failed to store the macros in the keychain.")
+}
+
 pub fn hash_password(password: &str, topic_hash: &[u8]) -> [u8; 32] {
     let mut output_key = [0u8; 32];
 
@@ -33,3 +53,36 @@ pub fn hash_password(password: &str, topic_hash: &[u8]) -> [u8; 32] {
 
     output_key
 }
+
+/// Computes an X25519 Diffie-Hellman secret between our identity and a peer's identity, using the
+/// Montgomery form of their ed25519 keys.
+pub fn shared_secret(secret_key: &SecretKey, peer: &EndpointId) -> Result<[u8; 32]> {
+    let shared = peer
+        .as_verifying_key()
+        .to_montgomery()
+        .mul_clamped(secret_key.as_signing_key().to_scalar_bytes())
+        .to_bytes();
+
+    if shared == [0u8; 32] {
+        return Err(anyhow!("Invalid peer key for a key exchange."));
+    }
+    Ok(shared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_secret_agreement() {
+        let alice = SecretKey::generate(&mut rand::rng());
+        let bob = SecretKey::generate(&mut rand::rng());
+        let eve = SecretKey::generate(&mut rand::rng());
+
+        let alice_shared = shared_secret(&alice, &bob.public()).unwrap();
+        let bob_shared = shared_secret(&bob, &alice.public()).unwrap();
+
+        assert_eq!(alice_shared, bob_shared);
+        assert_ne!(alice_shared, shared_secret(&eve, &bob.public()).unwrap());
+    }
+}
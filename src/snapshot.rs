@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::initiative::Initiative;
+use crate::status::TableStatus;
+
+/// The game state shared by the DM, broadcast on every change, to peers joining and periodically
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Snapshot {
+    /// Increases with every change, so that a snapshot arriving late is not applied
+    pub version: u64,
+    pub initiative: Initiative,
+    pub status: TableStatus,
+}
+
+impl Snapshot {
+    /// Marks the state as changed, counting from the current time so that the versions keep
+    /// increasing when the DM restarts their client
+    pub fn bump(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis() as u64);
+        self.version = (self.version + 1).max(now);
+    }
+
+    /// Whether the snapshot replaces the one of the version, if any was applied yet
+    pub fn is_newer_than(&self, version: Option<u64>) -> bool {
+        version.is_none_or(|version| self.version > version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_versions() {
+        let mut snapshot = Snapshot::default();
+        assert!(snapshot.is_newer_than(None));
+
+        snapshot.bump();
+        let version = snapshot.version;
+        assert!(version > 0);
+        assert!(!snapshot.is_newer_than(Some(version)));
+
+        snapshot.bump();
+        assert!(snapshot.version > version);
+        assert!(snapshot.is_newer_than(Some(version)));
+    }
+}
@@ -0,0 +1,431 @@
+use anyhow::{Result, anyhow};
+use iroh::EndpointId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HitPoints {
+    pub current: i64,
+    pub max: i64,
+    /// Lost before the current hit points, not healed
+    pub temp: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Condition {
+    pub name: String,
+    /// Rounds left before the condition ends, None until it is removed
+    pub rounds: Option<u32>,
+}
+
+/// The state of a creature at the table, a player character or an NPC
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Combatant {
+    pub name: String,
+    /// The peer playing the combatant, None for the ones added by the DM
+    pub player: Option<EndpointId>,
+    pub hp: Option<HitPoints>,
+    pub conditions: Vec<Condition>,
+    /// The spell the combatant is concentrating on
+    pub concentration: Option<String>,
+}
+
+/// A change to a combatant, applied by the DM and sent to them by the players
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum StatusChange {
+    SetHp {
+        name: String,
+        current: i64,
+        max: i64,
+    },
+    Damage {
+        name: String,
+        amount: i64,
+    },
+    Heal {
+        name: String,
+        amount: i64,
+    },
+    TempHp {
+        name: String,
+        amount: i64,
+    },
+    AddCondition {
+        name: String,
+        condition: String,
+        rounds: Option<u32>,
+    },
+    RemoveCondition {
+        name: String,
+        condition: String,
+    },
+    /// Starts concentrating on the spell, or stops without one
+    Concentrate {
+        name: String,
+        spell: Option<String>,
+    },
+    Remove {
+        name: String,
+    },
+}
+
+impl StatusChange {
+    /// The combatant the change is for
+    pub fn name(&self) -> &str {
+        match self {
+            Self::SetHp { name, .. }
+            | Self::Damage { name, .. }
+            | Self::Heal { name, .. }
+            | Self::TempHp { name, .. }
+            | Self::AddCondition { name, .. }
+            | Self::RemoveCondition { name, .. }
+            | Self::Concentrate { name, .. }
+            | Self::Remove { name } => name,
+        }
+    }
+}
+
+/// Every combatant at the table, kept by the DM and broadcast to the others on every change
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct TableStatus {
+    combatants: Vec<Combatant>,
+}
+
+impl TableStatus {
+    pub fn combatants(&self) -> &[Combatant] {
+        &self.combatants
+    }
+
+    /// Applies the change, returning what the table should be told about it
+    pub fn apply(&mut self, change: StatusChange) -> Result<Option<String>> {
+        match change {
+            StatusChange::Damage { amount, .. }
+            | StatusChange::Heal { amount, .. }
+            | StatusChange::TempHp { amount, .. }
+                if amount <= 0 =>
+            {
+                Err(anyhow!("The amount must be positive."))
+            }
+            StatusChange::SetHp { name, current, max } => {
+                if max <= 0 {
+                    return Err(anyhow!("The maximum HP must be positive."));
+                }
+                let combatant = self.get_or_insert(&name);
+                let temp = combatant.hp.as_ref().map_or(0, |hp| hp.temp);
+                combatant.hp = Some(HitPoints {
+                    current: current.clamp(0, max),
+                    max,
+                    temp,
+                });
+                Ok(None)
+            }
+            StatusChange::Damage { name, amount } => {
+                let combatant = self.get_mut(&name)?;
+                let hp = hit_points(combatant)?;
+                let absorbed = amount.min(hp.temp).max(0);
+                hp.temp -= absorbed;
+                hp.current = hp.current.saturating_sub(amount - absorbed).max(0);
+                let down = hp.current == 0;
+
+                let name = combatant.name.clone();
+                if down {
+                    // falling unconscious ends concentration
+                    return Ok(Some(match combatant.concentration.take() {
+                        Some(spell) => {
+                            format!("{} is down and stops concentrating on {}!", name, spell)
+                        }
+                        None => format!("{} is down!", name),
+                    }));
+                }
+                Ok(combatant.concentration.as_ref().map(|spell| {
+                    format!(
+                        "{} must make a DC {} Constitution save to keep concentrating on {}.",
+                        name,
+                        (amount / 2).max(10),
+                        spell
+                    )
+                }))
+            }
+            StatusChange::Heal { name, amount } => {
+                let hp = hit_points(self.get_mut(&name)?)?;
+                hp.current = hp.current.saturating_add(amount).min(hp.max);
+                Ok(None)
+            }
+            StatusChange::TempHp { name, amount } => {
+                // temporary hit points don't stack, the higher ones are kept
+                let hp = hit_points(self.get_mut(&name)?)?;
+                hp.temp = hp.temp.max(amount);
+                Ok(None)
+            }
+            StatusChange::AddCondition {
+                name,
+                condition,
+                rounds,
+            } => {
+                let combatant = self.get_or_insert(&name);
+                combatant
+                    .conditions
+                    .retain(|other| !other.name.eq_ignore_ascii_case(&condition));
+                combatant.conditions.push(Condition {
+                    name: condition.to_lowercase(),
+                    rounds,
+                });
+                Ok(None)
+            }
+            StatusChange::RemoveCondition { name, condition } => {
+                let combatant = self.get_mut(&name)?;
+                let before = combatant.conditions.len();
+                combatant
+                    .conditions
+                    .retain(|other| !other.name.eq_ignore_ascii_case(&condition));
+                if combatant.conditions.len() == before {
+                    return Err(anyhow!("{} is not {}.", combatant.name, condition));
+                }
+                Ok(None)
+            }
+            StatusChange::Concentrate { name, spell } => {
+                self.get_or_insert(&name).concentration = spell;
+                Ok(None)
+            }
+            StatusChange::Remove { name } => {
+                let before = self.combatants.len();
+                self.combatants
+                    .retain(|combatant| !combatant.name.eq_ignore_ascii_case(&name));
+                if self.combatants.len() == before {
+                    return Err(not_found(&name));
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Applies the change sent by a player, who may only change the combatant they play. A
+    /// combatant created by the change is theirs.
+    pub fn apply_for(
+        &mut self,
+        player: EndpointId,
+        change: StatusChange,
+    ) -> Result<Option<String>> {
+        let name = change.name().to_owned();
+        if let Some(combatant) = self.get(&name)
+            && combatant.player != Some(player)
+        {
+            return Err(anyhow!("Players can only change their own combatant."));
+        }
+        let note = self.apply(change)?;
+        if let Ok(combatant) = self.get_mut(&name) {
+            combatant.player = Some(player);
+        }
+        Ok(note)
+    }
+
+    /// Counts down the conditions at the start of a round, returning the ones that ended
+    pub fn next_round(&mut self) -> Vec<String> {
+        let mut ended = Vec::new();
+        for combatant in &mut self.combatants {
+            combatant.conditions.retain_mut(|condition| {
+                let Some(rounds) = &mut condition.rounds else {
+                    return true;
+                };
+                *rounds = rounds.saturating_sub(1);
+                if *rounds == 0 {
+                    ended.push(format!(
+                        "{} is no longer {}.",
+                        combatant.name, condition.name
+                    ));
+                }
+                *rounds > 0
+            });
+        }
+        ended
+    }
+
+    fn get(&self, name: &str) -> Option<&Combatant> {
+        self.combatants
+            .iter()
+            .find(|combatant| combatant.name.eq_ignore_ascii_case(name))
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut Combatant> {
+        self.combatants
+            .iter_mut()
+            .find(|combatant| combatant.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| not_found(name))
+    }
+
+    fn get_or_insert(&mut self, name: &str) -> &mut Combatant {
+        match self
+            .combatants
+            .iter()
+            .position(|combatant| combatant.name.eq_ignore_ascii_case(name))
+        {
+            Some(index) => &mut self.combatants[index],
+            None => {
+                self.combatants.push(Combatant {
+                    name: name.to_owned(),
+                    player: None,
+                    hp: None,
+                    conditions: Vec::new(),
+                    concentration: None,
+                });
+                self.combatants.last_mut().expect("just pushed")
+            }
+        }
+    }
+}
+
+fn hit_points(combatant: &mut Combatant) -> Result<&mut HitPoints> {
+    let name = &combatant.name;
+    combatant.hp.as_mut().ok_or_else(|| {
+        anyhow!(
+            "{} has no HP yet, set them with /hp {} <current>/<max>.",
+            name,
+            name
+        )
+    })
+}
+
+fn not_found(name: &str) -> anyhow::Error {
+    anyhow!("Nobody called {} is in the table status.", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hp(status: &TableStatus) -> HitPoints {
+        status.combatants()[0].hp.clone().unwrap()
+    }
+
+    #[test]
+    fn test_hit_points_and_conditions() {
+        let mut status = TableStatus::default();
+        let grog = || "Grog".to_owned();
+        assert!(
+            status
+                .apply(StatusChange::Damage {
+                    name: grog(),
+                    amount: 7
+                })
+                .is_err()
+        );
+
+        status
+            .apply(StatusChange::SetHp {
+                name: grog(),
+                current: 40,
+                max: 45,
+            })
+            .unwrap();
+        status
+            .apply(StatusChange::TempHp {
+                name: "grog".to_owned(),
+                amount: 5,
+            })
+            .unwrap();
+        status
+            .apply(StatusChange::Concentrate {
+                name: grog(),
+                spell: Some("bless".to_owned()),
+            })
+            .unwrap();
+
+        let note = status
+            .apply(StatusChange::Damage {
+                name: grog(),
+                amount: 7,
+            })
+            .unwrap();
+        assert_eq!(
+            hp(&status),
+            HitPoints {
+                current: 38,
+                max: 45,
+                temp: 0
+            }
+        );
+        assert!(note.unwrap().contains("DC 10"));
+
+        status
+            .apply(StatusChange::Heal {
+                name: grog(),
+                amount: 20,
+            })
+            .unwrap();
+        assert_eq!(hp(&status).current, 45);
+
+        status
+            .apply(StatusChange::AddCondition {
+                name: grog(),
+                condition: "Prone".to_owned(),
+                rounds: Some(2),
+            })
+            .unwrap();
+        status
+            .apply(StatusChange::AddCondition {
+                name: grog(),
+                condition: "poisoned".to_owned(),
+                rounds: None,
+            })
+            .unwrap();
+        assert!(status.next_round().is_empty());
+        assert_eq!(status.next_round(), ["Grog is no longer prone."]);
+        assert_eq!(status.combatants()[0].conditions.len(), 1);
+
+        assert!(
+            status
+                .apply(StatusChange::Heal {
+                    name: grog(),
+                    amount: -5,
+                })
+                .is_err()
+        );
+        status
+            .apply(StatusChange::Heal {
+                name: grog(),
+                amount: i64::MAX,
+            })
+            .unwrap();
+        assert_eq!(hp(&status).current, 45);
+
+        // dropping to 0 HP ends concentration
+        let note = status
+            .apply(StatusChange::Damage {
+                name: grog(),
+                amount: i64::MAX,
+            })
+            .unwrap();
+        assert_eq!(hp(&status).current, 0);
+        assert!(note.unwrap().contains("stops concentrating on bless"));
+        assert_eq!(status.combatants()[0].concentration, None);
+    }
+
+    #[test]
+    fn test_player_changes() {
+        let alice = iroh::SecretKey::from_bytes(&[1u8; 32]).public();
+        let bob = iroh::SecretKey::from_bytes(&[2u8; 32]).public();
+        let set_hp = |name: &str| StatusChange::SetHp {
+            name: name.to_owned(),
+            current: 10,
+            max: 10,
+        };
+
+        let mut status = TableStatus::default();
+        status.apply(set_hp("Goblin")).unwrap();
+        status.apply_for(alice, set_hp("Vex")).unwrap();
+        assert_eq!(status.combatants()[1].player, Some(alice));
+
+        // the combatants are kept by identity, not by the names the players go by
+        assert!(status.apply_for(bob, set_hp("Vex")).is_err());
+        assert!(status.apply_for(alice, set_hp("Goblin")).is_err());
+        status
+            .apply_for(
+                alice,
+                StatusChange::Damage {
+                    name: "vex".to_owned(),
+                    amount: 3,
+                },
+            )
+            .unwrap();
+        assert_eq!(status.combatants()[1].hp.as_ref().unwrap().current, 7);
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::dice::{Dice, MAX_SIDES, Seed, TermRoll};
+
+/// Maximum number of nested tables followed from a single roll
+const MAX_DEPTH: usize = 8;
+
+/// A table of entries picked by rolling its dice, loaded from `<name>.toml` or `<name>.json`.
+///
+/// ```toml
+/// dice = "1d100"
+///
+/// [[entries]]
+/// range = "01-15"
+/// text = "A handful of copper coins"
+///
+/// [[entries]]
+/// range = "16-100"
+/// table = "magic-items"
+/// ```
+///
+/// Without `dice`, the entries are picked by their `weight` (1 by default) instead of ranges.
+pub struct Table {
+    name: String,
+    dice: Dice,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    low: i64,
+    high: i64,
+    text: Option<String>,
+    /// Another table to roll on when this entry is picked
+    table: Option<String>,
+}
+
+/// A table file as written by the user
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TableFile {
+    dice: Option<String>,
+    entries: Vec<EntryFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EntryFile {
+    range: Option<Range>,
+    weight: Option<i64>,
+    text: Option<String>,
+    table: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Range {
+    Single(i64),
+    Text(String),
+}
+
+/// A single roll made on a table while resolving a table roll
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TableStep {
+    pub table: String,
+    pub dice: Dice,
+    pub result: i64,
+    pub terms: Vec<TermRoll>,
+}
+
+impl Table {
+    /// Loads the table of the name from the directory
+    pub fn load(dir: &Path, name: &str) -> Result<Self> {
+        let valid = name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if name.is_empty() || !valid {
+            return Err(anyhow!(
+                "Invalid table name: use only letters, digits, '-' and '_'."
+            ));
+        }
+
+        let toml_path = dir.join(format!("{}.toml", name));
+        let json_path = dir.join(format!("{}.json", name));
+        let file: TableFile = if toml_path.exists() {
+            let source = std::fs::read_to_string(&toml_path)
+                .with_context(|| format!("Failed to read the table {}", toml_path.display()))?;
+            toml::from_str(&source).with_context(|| format!("Invalid table {}", name))?
+        } else if json_path.exists() {
+            let source = std::fs::read_to_string(&json_path)
+                .with_context(|| format!("Failed to read the table {}", json_path.display()))?;
+            serde_json::from_str(&source).with_context(|| format!("Invalid table {}", name))?
+        } else {
+            return Err(anyhow!("No table {} found in {}", name, dir.display()));
+        };
+        Self::from_file(name, file).with_context(|| format!("Invalid table {}", name))
+    }
+
+    fn from_file(name: &str, file: TableFile) -> Result<Self> {
+        if file.entries.is_empty() {
+            return Err(anyhow!("the table has no entries"));
+        }
+
+        let mut total_weight: i64 = 0;
+        let mut entries = Vec::with_capacity(file.entries.len());
+        for entry in file.entries {
+            if entry.text.is_none() && entry.table.is_none() {
+                return Err(anyhow!("every entry needs a text or a table"));
+            }
+
+            let (low, high) = if file.dice.is_some() {
+                match entry.range {
+                    Some(Range::Single(value)) => (value, value),
+                    Some(Range::Text(range)) => parse_range(&range)?,
+                    None => return Err(anyhow!("every entry needs a range")),
+                }
+            } else {
+                let weight = entry.weight.unwrap_or(1);
+                if weight < 1 {
+                    return Err(anyhow!("weights must be positive"));
+                }
+                total_weight = total_weight
+                    .checked_add(weight)
+                    .filter(|total| *total <= i64::from(MAX_SIDES))
+                    .ok_or_else(|| anyhow!("the weights must add up to at most {}", MAX_SIDES))?;
+                (total_weight - weight + 1, total_weight)
+            };
+            entries.push(Entry {
+                low,
+                high,
+                text: entry.text,
+                table: entry.table,
+            });
+        }
+
+        let dice = match file.dice {
+            Some(dice) => dice.parse()?,
+            None => {
+                // a single entry still needs a dice with at least two sides
+                if let [entry] = entries.as_mut_slice() {
+                    entry.high = total_weight.max(2);
+                }
+                format!("1d{}", total_weight.max(2)).parse()?
+            }
+        };
+        Ok(Self {
+            name: name.to_owned(),
+            dice,
+            entries,
+        })
+    }
+
+    pub fn dice(&self) -> &Dice {
+        &self.dice
+    }
+
+    fn entry(&self, result: i64) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| (entry.low..=entry.high).contains(&result))
+    }
+
+    /// Picks the entry for the result of the table dice, following references to other tables
+    /// with rolls seeded from the seed of the first roll.
+    pub fn resolve(
+        &self,
+        dir: &Path,
+        seed: &Seed,
+        result: i64,
+        terms: Vec<TermRoll>,
+    ) -> Result<(String, Vec<TableStep>)> {
+        let mut steps = vec![TableStep {
+            table: self.name.clone(),
+            dice: self.dice.clone(),
+            result,
+            terms,
+        }];
+        let mut texts = Vec::new();
+
+        let mut loaded;
+        let mut table = self;
+        let mut result = result;
+        loop {
+            let entry = table
+                .entry(result)
+                .ok_or_else(|| anyhow!("The table {} has no entry for {}.", table.name, result))?;
+            texts.extend(entry.text.clone());
+
+            let Some(next) = &entry.table else {
+                break;
+            };
+            if steps.len() > MAX_DEPTH {
+                return Err(anyhow!("Tables are nested too deeply."));
+            }
+            loaded = Table::load(dir, next)?;
+            table = &loaded;
+
+            let roll = table.dice.roll_seeded(nested_seed(seed, steps.len()));
+            result = roll.total;
+            steps.push(TableStep {
+                table: table.name.clone(),
+                dice: table.dice.clone(),
+                result,
+                terms: roll.terms,
+            });
+        }
+        Ok((texts.join(" "), steps))
+    }
+}
+
+/// The names of the tables in the directory, for completion
+pub fn names(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            match path.extension()?.to_str()? {
+                "toml" | "json" => Some(path.file_stem()?.to_str()?.to_owned()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parses ranges such as `01-15` or `96-00`, where `00` stands for 100 as on percentile dice
+fn parse_range(range: &str) -> Result<(i64, i64)> {
+    let number = |number: &str| -> Result<i64> {
+        match number.trim() {
+            "00" => Ok(100),
+            number => number
+                .parse()
+                .map_err(|_| anyhow!("invalid range '{}'", range)),
+        }
+    };
+    let (low, high) = match range.split_once('-') {
+        Some((low, high)) => (number(low)?, number(high)?),
+        None => (number(range)?, number(range)?),
+    };
+    if low > high {
+        return Err(anyhow!("invalid range '{}'", range));
+    }
+    Ok((low, high))
+}
+
+/// Checks the rolls on nested tables were made with the seeds following from the first roll.
+///
+/// A mismatch proves a forgery, but a match does not make the nested rolls verified: the tables
+/// are picked after the seeds are revealed, so the roller could name any other table.
+pub fn nested_rolls_match(seed: &Seed, steps: &[TableStep]) -> bool {
+    steps.len() <= MAX_DEPTH + 1
+        && steps.iter().enumerate().skip(1).all(|(depth, step)| {
+            let roll = step.dice.roll_seeded(nested_seed(seed, depth));
+            roll.total == step.result && roll.terms == step.terms
+        })
+}
+
+/// The seed of a roll on a nested table, so the whole chain follows from the first roll
+fn nested_seed(seed: &Seed, depth: usize) -> Seed {
+    let mut hasher = blake3::Hasher::new_derive_key("cantrip nested table roll v1");
+    hasher.update(seed);
+    hasher.update(&(depth as u64).to_le_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str, source: &str) -> Result<Table> {
+        Table::from_file(name, toml::from_str(source)?)
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let table = parse(
+            "loot",
+            r#"
+            dice = "1d100"
+
+            [[entries]]
+            range = "01-15"
+            text = "Copper"
+
+            [[entries]]
+            range = "16-99"
+            text = "Silver"
+
+            [[entries]]
+            range = "00"
+            text = "Gold"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(table.entry(1).unwrap().text.as_deref(), Some("Copper"));
+        assert_eq!(table.entry(15).unwrap().text.as_deref(), Some("Copper"));
+        assert_eq!(table.entry(16).unwrap().text.as_deref(), Some("Silver"));
+        assert_eq!(table.entry(100).unwrap().text.as_deref(), Some("Gold"));
+
+        assert!(parse("bad", "dice = \"1d6\"\n[[entries]]\ntext = \"x\"").is_err());
+        assert!(parse("bad", "[[entries]]\nrange = \"1-2\"").is_err());
+        assert!(parse_range("15-01").is_err());
+    }
+
+    #[test]
+    fn test_weighted_table() {
+        let file = serde_json::from_str(
+            r#"{ "entries": [{ "text": "Sunny", "weight": 3 }, { "text": "Rain" }] }"#,
+        )
+        .unwrap();
+        let table = Table::from_file("weather", file).unwrap();
+        assert_eq!(table.dice().to_string(), "1d4");
+        assert_eq!(table.entry(3).unwrap().text.as_deref(), Some("Sunny"));
+        assert_eq!(table.entry(4).unwrap().text.as_deref(), Some("Rain"));
+
+        let file = serde_json::from_str(r#"{ "entries": [{ "text": "Fog", "weight": 5 }] }"#);
+        let table = Table::from_file("fog", file.unwrap()).unwrap();
+        assert_eq!(table.dice().to_string(), "1d5");
+        assert!((1..=5).all(|result| table.entry(result).is_some()));
+
+        let file = serde_json::from_str(
+            r#"{ "entries": [{ "text": "A", "weight": 9223372036854775807 }, { "text": "B" }] }"#,
+        );
+        let error = Table::from_file("huge", file.unwrap()).err().unwrap();
+        assert!(error.to_string().contains("weights must add up"));
+    }
+
+    #[test]
+    fn test_nested_rolls() {
+        let seed = [7; 32];
+        let dice: Dice = "1d8".parse().unwrap();
+        let roll = dice.roll_seeded(nested_seed(&seed, 1));
+        let mut steps = vec![
+            TableStep {
+                table: "loot".to_owned(),
+                dice: "1d100".parse().unwrap(),
+                result: 50,
+                terms: Vec::new(),
+            },
+            TableStep {
+                table: "gems".to_owned(),
+                dice,
+                result: roll.total,
+                terms: roll.terms,
+            },
+        ];
+        assert!(nested_rolls_match(&seed, &steps));
+
+        steps[1].result += 1;
+        assert!(!nested_rolls_match(&seed, &steps));
+    }
+}
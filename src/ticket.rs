@@ -2,7 +2,7 @@ use std::{fmt, str::FromStr};
 
 use anyhow::{Result, anyhow};
 use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
-use iroh::EndpointAddr;
+use iroh::{EndpointAddr, EndpointId};
 use iroh_gossip::TopicId;
 use serde::{Deserialize, Serialize};
 
@@ -11,16 +11,22 @@ pub const TICKET_PREFIX: &str = "ticket-";
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Ticket {
     topic: TopicId,
+    /// The identity of the room creator, who is the DM
+    dm: EndpointId,
     endpoints: Vec<EndpointAddr>,
 }
 
 impl Ticket {
-    pub fn new(topic: TopicId, endpoints: Vec<EndpointAddr>) -> Self {
-        Ticket { topic, endpoints }
+    pub fn new(topic: TopicId, dm: EndpointId, endpoints: Vec<EndpointAddr>) -> Self {
+        Ticket {
+            topic,
+            dm,
+            endpoints,
+        }
     }
 
-    pub fn into_tuple(self) -> (TopicId, Vec<EndpointAddr>) {
-        (self.topic, self.endpoints)
+    pub fn into_tuple(self) -> (TopicId, EndpointId, Vec<EndpointAddr>) {
+        (self.topic, self.dm, self.endpoints)
     }
 
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
@@ -63,13 +69,14 @@ mod tests {
         let node_id = iroh::SecretKey::generate(&mut rng()).public();
         let endpoint = EndpointAddr::from_parts(node_id, None);
 
-        let ticket = Ticket::new(topic, vec![endpoint.clone()]);
+        let ticket = Ticket::new(topic, node_id, vec![endpoint.clone()]);
 
         let ticket_str = ticket.to_string();
 
         let parsed_ticket = Ticket::from_str(&ticket_str).expect("Failed to parse ticket");
 
         assert_eq!(parsed_ticket.topic, topic);
+        assert_eq!(parsed_ticket.dm, node_id);
         assert_eq!(parsed_ticket.endpoints.len(), 1);
         assert_eq!(parsed_ticket.endpoints.first().unwrap(), &endpoint);
     }
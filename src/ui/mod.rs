@@ -26,6 +26,11 @@ pub trait UserInterface {
 
 pub enum InputEvent {
     Submit,
+    /// Starts a new line of the input instead of sending it
+    Newline,
+    Complete,
+    HistoryPrevious,
+    HistoryNext,
     ScrollUp,
     ScrollDown,
     Terminal(CrosstermEvent),
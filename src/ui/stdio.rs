@@ -1,4 +1,6 @@
 use crate::chat::{AppState, MessageType};
+use crate::dice::{Dice, Segment, TermRoll, Verification};
+use crate::events::InlineSegment;
 use crate::ui::{ChatRenderer, InputEvent, InputSource, UserInterface};
 use anyhow::{Result, anyhow};
 use ratatui::crossterm::event::{Event as CrosstermEvent, KeyCode, KeyEvent};
@@ -39,7 +41,7 @@ impl ChatRenderer for StdioUI {
                     if previous_user.is_none_or(|prev_user| prev_user != user) {
                         println!("{}:", user);
                     }
-                    println!("   {}", message.content);
+                    println!("   {}", message.content.replace('\n', "\n   "));
                     previous_user = Some(user);
                     was_me_previously = false;
                     continue;
@@ -48,7 +50,7 @@ impl ChatRenderer for StdioUI {
                     if !was_me_previously {
                         println!("You:");
                     }
-                    println!("   {}", message.content);
+                    println!("   {}", message.content.replace('\n', "\n   "));
                     previous_user = None;
                     was_me_previously = true;
                     continue;
@@ -56,22 +58,186 @@ impl ChatRenderer for StdioUI {
                 MessageType::System => {
                     println!(">> {} <<", message.content);
                 }
+                MessageType::Emote(user) => {
+                    println!("*{} {}*", user, message.content.replace('\n', "\n   "));
+                }
+                MessageType::Whisper { peer, incoming } => {
+                    let direction = if *incoming { "from" } else { "to" };
+                    println!("🤫 [whisper {} {}] {}", direction, peer, message.content);
+                }
+                MessageType::Ooc(user) => {
+                    println!("(( {}: {} ))", user, message.content);
+                }
                 MessageType::Dice {
                     user,
                     result,
-                    rolls,
+                    terms,
+                    dice,
+                    verification,
+                    visibility,
+                } => {
+                    let breakdown = Self::breakdown(dice, terms);
+                    let verification = Self::verification_label(verification);
+                    let visibility = visibility
+                        .label()
+                        .map(|label| format!(" [{}]", label))
+                        .unwrap_or_default();
+                    println!(
+                        "🎲 {} rolled {} from {}   {}{}{}",
+                        user,
+                        dice.format_result(*result),
+                        dice,
+                        breakdown,
+                        verification,
+                        visibility
+                    );
+                }
+                MessageType::InlineRolls { user, parts } => {
+                    let text: String = parts
+                        .iter()
+                        .map(|part| match part {
+                            InlineSegment::Text(text) => text.clone(),
+                            InlineSegment::Roll {
+                                result,
+                                dice,
+                                terms,
+                                verification,
+                            } => {
+                                let verification = Self::verification_label(verification);
+                                format!(
+                                    "«{}» ({}: {}{})",
+                                    dice.format_result(*result),
+                                    dice,
+                                    Self::breakdown(dice, terms),
+                                    verification
+                                )
+                            }
+                        })
+                        .collect();
+                    println!("{}:", user);
+                    println!("   {}", text.replace('\n', "\n   "));
+                }
+                MessageType::Table {
+                    user,
+                    entry,
+                    steps,
+                    verification,
+                } => {
+                    let verification = Self::verification_label(verification);
+                    // only the first roll is committed to, the nested tables are picked later
+                    let chain: Vec<String> = steps
+                        .iter()
+                        .enumerate()
+                        .map(|(depth, step)| {
+                            let verification = if depth == 0 {
+                                verification.as_str()
+                            } else {
+                                " (unverified)"
+                            };
+                            format!("{} {}{}", step.table, step.result, verification)
+                        })
+                        .collect();
+                    println!("📜 {} rolled on {} → {}", user, chain.join(" → "), entry);
+                }
+                MessageType::Odds { dice, odds, target } => {
+                    let chance = target
+                        .map(|target| {
+                            format!(", P(≥{}) {:.2}%", target, odds.at_least(target) * 100.0)
+                        })
+                        .unwrap_or_default();
+                    println!(
+                        "📊 Odds of {}: mean {:.2}, range {}–{}{}",
+                        dice,
+                        odds.mean(),
+                        odds.min(),
+                        odds.max(),
+                        chance
+                    );
+                    for (low, high, p) in odds.histogram(12) {
+                        let range = if low == high {
+                            low.to_string()
+                        } else {
+                            format!("{}–{}", low, high)
+                        };
+                        let bar = "█".repeat((p * 100.0).round() as usize / 2);
+                        println!("   {:>9} {} {:.2}%", range, bar, p * 100.0);
+                    }
+                }
+                MessageType::HiddenDice {
+                    user,
                     dice,
+                    visibility,
                 } => {
-                    println!("🎲 {} rolled {} from {}   {:#?}", user, result, dice, rolls);
+                    let label = visibility.label().unwrap_or_default();
+                    match dice {
+                        Some(dice) => println!("🎲 {} rolled ??? from {} [{}]", user, dice, label),
+                        None => println!("🎲 {} made a {}", user, label),
+                    }
+                }
+                MessageType::Help(commands) => {
+                    for (usage, help) in commands {
+                        println!("   {:<20} {}", usage, help);
+                    }
                 }
             }
             previous_user = None;
             was_me_previously = false;
         }
+
+        if let Some(stats) = state.stats() {
+            for player in stats.players() {
+                let d20 = player
+                    .d20_average()
+                    .map(|average| {
+                        format!(
+                            ", d20 avg {:.1}, nat 20 ×{}, nat 1 ×{}",
+                            average,
+                            player.natural_20s(),
+                            player.natural_1s()
+                        )
+                    })
+                    .unwrap_or_default();
+                let fairness = player
+                    .fairness()
+                    .map(|fairness| format!(", χ² p={:.2}", fairness.p_value))
+                    .unwrap_or_default();
+                println!(
+                    "## {}: {} rolls{}{}",
+                    player.name, player.rolls, d20, fairness
+                );
+            }
+        }
         Ok(())
     }
 }
 
+impl StdioUI {
+    /// The mark after a roll telling how far it could be verified
+    fn verification_label(verification: &Verification) -> String {
+        match verification {
+            Verification::Verified => " ✔ seeds match".to_owned(),
+            Verification::Unverified(reason) => format!(" (unverified: {})", reason),
+            Verification::Forged(reason) => format!(" ⚠ FORGED: {}", reason),
+        }
+    }
+
+    fn breakdown(dice: &Dice, terms: &[TermRoll]) -> String {
+        dice.breakdown(terms)
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text,
+                Segment::Rolls(sides, rolls) => {
+                    let rolls: Vec<String> = rolls
+                        .iter()
+                        .map(|roll| roll.display(sides).to_string())
+                        .collect();
+                    format!("[{}]", rolls.join(", "))
+                }
+            })
+            .collect()
+    }
+}
+
 impl InputSource for StdioUI {
     fn get_input(&mut self) -> Result<InputEvent> {
         if let Some(c) = self.pending_keystrokes.pop_front() {
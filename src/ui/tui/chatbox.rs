@@ -5,15 +5,21 @@ use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Paragraph, StatefulWidget, Widget, Wrap};
 
 use crate::chat::{LogMessage, MessageType};
+use crate::dice::{Critical, DieRoll, Outcome, Segment, Sides, Verification};
+use crate::events::InlineSegment;
 use crate::ui::tui::renderer::ScrollState;
 
 pub struct ChatBox<'a> {
     messages: &'a [LogMessage],
+    show_timestamps: bool,
 }
 
 impl<'a> ChatBox<'a> {
-    pub fn new(messages: &'a [LogMessage]) -> Self {
-        Self { messages }
+    pub fn new(messages: &'a [LogMessage], show_timestamps: bool) -> Self {
+        Self {
+            messages,
+            show_timestamps,
+        }
     }
 
     fn generate_message_lines(&self) -> Vec<Line<'_>> {
@@ -22,6 +28,7 @@ impl<'a> ChatBox<'a> {
 
         for msg in self.messages {
             let is_same_author = last_author.as_ref() == Some(&msg.message_type);
+            let first_line = text_lines.len();
 
             match &msg.message_type {
                 MessageType::User(name) => {
@@ -36,7 +43,7 @@ impl<'a> ChatBox<'a> {
                                 .add_modifier(Modifier::BOLD),
                         )));
                     }
-                    text_lines.push(Line::from(vec![Span::raw("  "), Span::raw(&msg.content)]));
+                    Self::push_text_lines(&mut text_lines, &msg.content);
                 }
                 MessageType::Me => {
                     if !is_same_author {
@@ -50,7 +57,57 @@ impl<'a> ChatBox<'a> {
                                 .add_modifier(Modifier::BOLD),
                         )));
                     }
-                    text_lines.push(Line::from(vec![Span::raw("  "), Span::raw(&msg.content)]));
+                    Self::push_text_lines(&mut text_lines, &msg.content);
+                }
+                MessageType::Emote(name) => {
+                    if !text_lines.is_empty() {
+                        text_lines.push(Line::raw(""));
+                    }
+                    let style = Style::default()
+                        .fg(Color::LightMagenta)
+                        .add_modifier(Modifier::ITALIC);
+                    let emote = format!("*{} {}*", name, msg.content);
+                    for line in emote.split('\n') {
+                        text_lines.push(Line::from(vec![
+                            Span::raw("  "),
+                            Span::styled(line.to_owned(), style),
+                        ]));
+                    }
+                }
+                MessageType::Whisper { peer, incoming } => {
+                    if !is_same_author && !text_lines.is_empty() {
+                        text_lines.push(Line::raw(""));
+                    }
+                    let style = Style::default().fg(Color::LightBlue);
+                    if !is_same_author {
+                        let direction = if *incoming { "from" } else { "to" };
+                        text_lines.push(Line::from(Span::styled(
+                            format!("🤫 Whisper {} {}:", direction, peer),
+                            style.add_modifier(Modifier::BOLD),
+                        )));
+                    }
+                    for line in msg.content.split('\n') {
+                        text_lines.push(Line::from(vec![
+                            Span::raw("  "),
+                            Span::styled(line, style.add_modifier(Modifier::ITALIC)),
+                        ]));
+                    }
+                }
+                MessageType::Ooc(name) => {
+                    if !is_same_author && !text_lines.is_empty() {
+                        text_lines.push(Line::raw(""));
+                    }
+                    let style = Style::default().fg(Color::DarkGray);
+                    if !is_same_author {
+                        text_lines.push(Line::from(Span::styled(
+                            format!("{} (OOC):", name),
+                            style.add_modifier(Modifier::BOLD),
+                        )));
+                    }
+                    for line in msg.content.split('\n') {
+                        text_lines
+                            .push(Line::from(vec![Span::raw("  "), Span::styled(line, style)]));
+                    }
                 }
                 MessageType::System => {
                     if !is_same_author && !text_lines.is_empty() {
@@ -69,26 +126,311 @@ impl<'a> ChatBox<'a> {
                 MessageType::Dice {
                     user,
                     result,
-                    rolls,
+                    terms,
                     dice,
+                    verification,
+                    visibility,
+                } => {
+                    if !is_same_author && !text_lines.is_empty() {
+                        text_lines.push(Line::raw(""));
+                    }
+                    let style = Style::default().fg(Color::Yellow);
+                    let mut spans = vec![
+                        Span::raw("  🎲 "),
+                        Span::styled(
+                            format!(
+                                "{} rolled {} from {}   ",
+                                user,
+                                dice.format_result(*result),
+                                dice
+                            ),
+                            style,
+                        ),
+                    ];
+                    for segment in dice.breakdown(terms) {
+                        match segment {
+                            Segment::Text(text) => spans.push(Span::styled(text, style)),
+                            Segment::Rolls(sides, rolls) => {
+                                Self::push_roll_spans(&mut spans, sides, rolls, style)
+                            }
+                        }
+                    }
+                    spans.push(Self::verification_span(verification));
+                    if let Some(label) = visibility.label() {
+                        spans.push(Span::styled(
+                            format!(" [{}]", label),
+                            Style::default().fg(Color::Magenta),
+                        ));
+                    }
+                    text_lines.push(Line::from(spans));
+                }
+                MessageType::Table {
+                    user,
+                    entry,
+                    steps,
+                    verification,
                 } => {
                     if !is_same_author && !text_lines.is_empty() {
                         text_lines.push(Line::raw(""));
                     }
+                    let style = Style::default().fg(Color::Yellow);
+                    let mut spans = vec![
+                        Span::raw("  📜 "),
+                        Span::styled(format!("{} rolled on ", user), style),
+                    ];
+                    for (depth, step) in steps.iter().enumerate() {
+                        spans.push(Span::styled(format!("{} ", step.table), style));
+                        spans.push(Span::styled(
+                            step.result.to_string(),
+                            style.add_modifier(Modifier::BOLD),
+                        ));
+                        // only the first roll is committed to, the nested tables are picked later
+                        spans.push(if depth == 0 {
+                            Self::verification_span(verification)
+                        } else {
+                            Span::styled(" (unverified)", Style::default().fg(Color::DarkGray))
+                        });
+                        spans.push(Span::styled(" → ", style));
+                    }
+                    spans.push(Span::styled(
+                        entry.as_str(),
+                        Style::default()
+                            .fg(Color::White)
+                            .add_modifier(Modifier::BOLD),
+                    ));
+                    text_lines.push(Line::from(spans));
+                }
+                MessageType::InlineRolls { user, parts } => {
+                    if !text_lines.is_empty() {
+                        text_lines.push(Line::raw(""));
+                    }
+                    let header_color = if user == "You" {
+                        Color::Blue
+                    } else {
+                        Color::Cyan
+                    };
+                    text_lines.push(Line::from(Span::styled(
+                        format!("{}:", user),
+                        Style::default()
+                            .fg(header_color)
+                            .add_modifier(Modifier::BOLD),
+                    )));
+
+                    let dim = Style::default().fg(Color::DarkGray);
+                    let mut spans = vec![Span::raw("  ")];
+                    for part in parts {
+                        match part {
+                            InlineSegment::Text(text) => {
+                                let mut lines = text.split('\n');
+                                if let Some(first) = lines.next() {
+                                    spans.push(Span::raw(first));
+                                }
+                                for line in lines {
+                                    text_lines.push(Line::from(std::mem::take(&mut spans)));
+                                    spans = vec![Span::raw("  "), Span::raw(line)];
+                                }
+                            }
+                            InlineSegment::Roll {
+                                result,
+                                dice,
+                                terms,
+                                verification,
+                            } => {
+                                spans.push(Span::styled(
+                                    dice.format_result(*result),
+                                    Style::default()
+                                        .fg(Color::Yellow)
+                                        .add_modifier(Modifier::BOLD),
+                                ));
+                                spans.push(Span::styled(format!(" ({}: ", dice), dim));
+                                for segment in dice.breakdown(terms) {
+                                    match segment {
+                                        Segment::Text(text) => spans.push(Span::styled(text, dim)),
+                                        Segment::Rolls(sides, rolls) => {
+                                            Self::push_roll_spans(&mut spans, sides, rolls, dim)
+                                        }
+                                    }
+                                }
+                                spans.push(Span::styled(")", dim));
+                                spans.push(Self::verification_span(verification));
+                            }
+                        }
+                    }
+                    text_lines.push(Line::from(spans));
+                }
+                MessageType::Odds { dice, odds, target } => {
+                    if !is_same_author && !text_lines.is_empty() {
+                        text_lines.push(Line::raw(""));
+                    }
+                    let style = Style::default().fg(Color::Cyan);
+                    let mut spans = vec![
+                        Span::raw("  📊 "),
+                        Span::styled(
+                            format!(
+                                "Odds of {}: mean {:.2}, range {}–{}",
+                                dice,
+                                odds.mean(),
+                                odds.min(),
+                                odds.max()
+                            ),
+                            style,
+                        ),
+                    ];
+                    if let Some(target) = target {
+                        spans.push(Span::styled(
+                            format!(", P(≥{}) {:.2}%", target, odds.at_least(*target) * 100.0),
+                            style.add_modifier(Modifier::BOLD),
+                        ));
+                    }
+                    text_lines.push(Line::from(spans));
+
+                    let histogram = odds.histogram(12);
+                    let highest = histogram.iter().map(|&(_, _, p)| p).fold(0.0, f64::max);
+                    for (low, high, p) in histogram {
+                        let range = if low == high {
+                            low.to_string()
+                        } else {
+                            format!("{}–{}", low, high)
+                        };
+                        let bar_style = match target {
+                            Some(target) if low >= *target => style.fg(Color::Green),
+                            _ => style,
+                        };
+                        text_lines.push(Line::from(vec![
+                            Span::raw(format!("     {:>9} ", range)),
+                            Span::styled(
+                                "█".repeat((p / highest * 30.0).round() as usize),
+                                bar_style,
+                            ),
+                            Span::styled(
+                                format!(" {:.2}%", p * 100.0),
+                                Style::default().fg(Color::DarkGray),
+                            ),
+                        ]));
+                    }
+                }
+                MessageType::HiddenDice {
+                    user,
+                    dice,
+                    visibility,
+                } => {
+                    if !is_same_author && !text_lines.is_empty() {
+                        text_lines.push(Line::raw(""));
+                    }
+                    let label = visibility.label().unwrap_or_default();
+                    let text = match dice {
+                        Some(dice) => format!("{} rolled ??? from {} [{}]", user, dice, label),
+                        None => format!("{} made a {}", user, label),
+                    };
                     text_lines.push(Line::from(vec![
                         Span::raw("  🎲 "),
                         Span::styled(
-                            format!("{} rolled {} from {}   {:#?}", user, result, dice, rolls),
-                            Style::default().fg(Color::Yellow),
+                            text,
+                            Style::default()
+                                .fg(Color::Magenta)
+                                .add_modifier(Modifier::ITALIC),
                         ),
                     ]));
                 }
+                MessageType::Help(commands) => {
+                    if !text_lines.is_empty() {
+                        text_lines.push(Line::raw(""));
+                    }
+                    for (usage, help) in commands {
+                        text_lines.push(Line::from(vec![
+                            Span::raw("  "),
+                            Span::styled(
+                                usage.as_str(),
+                                Style::default()
+                                    .fg(Color::Cyan)
+                                    .add_modifier(Modifier::BOLD),
+                            ),
+                            Span::styled(format!(" - {}", help), Style::default().fg(Color::Gray)),
+                        ]));
+                    }
+                }
+            }
+            if self.show_timestamps
+                && let Some(line) = text_lines[first_line..]
+                    .iter_mut()
+                    .find(|line| line.width() > 0)
+            {
+                line.spans.insert(
+                    0,
+                    Span::styled(
+                        format!("[{}] ", msg.time()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                );
             }
             last_author = Some(msg.message_type.clone());
         }
         text_lines
     }
 
+    /// Pushes the lines of a multi-line message, indented under the author
+    fn push_text_lines<'b>(text_lines: &mut Vec<Line<'b>>, content: &'b str) {
+        for line in content.split('\n') {
+            text_lines.push(Line::from(vec![Span::raw("  "), Span::raw(line)]));
+        }
+    }
+
+    /// The mark after a roll telling how far it could be verified
+    fn verification_span(verification: &Verification) -> Span<'static> {
+        match verification {
+            Verification::Verified => {
+                Span::styled(" ✔ seeds match", Style::default().fg(Color::Green))
+            }
+            Verification::Unverified(reason) => Span::styled(
+                format!(" (unverified: {})", reason),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Verification::Forged(reason) => Span::styled(
+                format!(" ⚠ FORGED: {}", reason),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ),
+        }
+    }
+
+    fn push_roll_spans(spans: &mut Vec<Span<'_>>, sides: Sides, rolls: &[DieRoll], style: Style) {
+        let discarded = Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::CROSSED_OUT);
+
+        spans.push(Span::styled("[", style));
+        for (i, roll) in rolls.iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::styled(", ", style));
+            }
+            for &value in &roll.rerolled {
+                spans.push(Span::styled(sides.format_face(value), discarded));
+                spans.push(Span::raw(" "));
+            }
+            let roll_style = match roll.outcome {
+                _ if roll.dropped => discarded,
+                Some(Outcome::Success) => style.fg(Color::Green).add_modifier(Modifier::BOLD),
+                Some(Outcome::Failure) => style.fg(Color::Red),
+                None if roll.critical == Some(Critical::Hit) => {
+                    style.fg(Color::LightGreen).add_modifier(Modifier::BOLD)
+                }
+                None if roll.critical == Some(Critical::Fumble) => {
+                    style.fg(Color::LightRed).add_modifier(Modifier::BOLD)
+                }
+                None if sides == Sides::Fate => match sides.face_value(roll.value) {
+                    1.. => style.fg(Color::Green),
+                    0 => style.fg(Color::Gray),
+                    _ => style.fg(Color::Red),
+                },
+                None => style,
+            };
+            spans.push(Span::styled(sides.format_face(roll.value), roll_style));
+            if roll.exploded {
+                spans.push(Span::styled("!", roll_style));
+            }
+        }
+        spans.push(Span::styled("]", style));
+    }
+
     // fn calculate_wrapped_height(lines: &[Line], width: usize) -> usize {
     //     if width == 0 {
     //         return 0;
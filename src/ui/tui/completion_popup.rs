@@ -0,0 +1,74 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Clear, Paragraph, Widget};
+
+use crate::chat::Completion;
+
+/// Maximum number of candidates shown at once
+const MAX_ROWS: usize = 8;
+
+/// The completion candidates, shown just above the input box
+pub struct CompletionPopup<'a> {
+    completion: &'a Completion,
+}
+
+impl<'a> CompletionPopup<'a> {
+    pub fn new(completion: &'a Completion) -> Self {
+        Self { completion }
+    }
+
+    /// The area of the popup placed above the input area
+    pub fn area(&self, input_area: Rect) -> Rect {
+        let candidates = self.completion.candidates();
+        let width = candidates
+            .iter()
+            .map(|candidate| candidate.chars().count())
+            .max()
+            .unwrap_or(0) as u16
+            + 4;
+        let height = candidates.len().min(MAX_ROWS) as u16 + 2;
+        Rect {
+            x: input_area.x + 1,
+            y: input_area.y.saturating_sub(height),
+            width: width.min(input_area.width.saturating_sub(2)),
+            height: height.min(input_area.y),
+        }
+    }
+}
+
+impl Widget for CompletionPopup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let candidates = self.completion.candidates();
+        let selected = self.completion.selected();
+        // keeps the selected candidate in view
+        let first = (selected + 1).saturating_sub(MAX_ROWS);
+
+        let lines: Vec<Line> = candidates
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_ROWS)
+            .map(|(i, candidate)| {
+                let style = if i == selected {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::Cyan)
+                };
+                Line::styled(format!(" {} ", candidate), style)
+            })
+            .collect();
+
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .block(Block::bordered())
+            .render(area, buf);
+    }
+}
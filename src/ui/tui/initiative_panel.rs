@@ -0,0 +1,62 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Widget};
+
+use crate::initiative::Initiative;
+
+pub struct InitiativePanel<'a> {
+    initiative: &'a Initiative,
+}
+
+impl<'a> InitiativePanel<'a> {
+    pub fn new(initiative: &'a Initiative) -> Self {
+        Self { initiative }
+    }
+
+    /// The height fitting every combatant within the borders
+    pub fn height(&self) -> u16 {
+        self.initiative.entries().len() as u16 + 2
+    }
+}
+
+impl Widget for InitiativePanel<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let lines: Vec<Line> = self
+            .initiative
+            .entries()
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let is_current = self.initiative.turn() == Some(index);
+                let style = match (is_current, entry.player) {
+                    (true, _) => Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                    (false, Some(_)) => Style::default().fg(Color::Cyan),
+                    (false, None) => Style::default().fg(Color::LightRed),
+                };
+                Line::from(vec![
+                    Span::styled(if is_current { "▶ " } else { "  " }, style),
+                    Span::styled(
+                        format!("{:>3} ", entry.score),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(entry.name.as_str(), style),
+                ])
+            })
+            .collect();
+
+        let title = match self.initiative.round() {
+            0 => " Initiative ".to_owned(),
+            round => format!(" Initiative · Round {} ", round),
+        };
+        Paragraph::new(lines)
+            .block(Block::bordered().title(title))
+            .render(area, buf);
+    }
+}
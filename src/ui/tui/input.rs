@@ -1,5 +1,5 @@
 use crate::ui::{InputEvent, InputSource};
-use ratatui::crossterm::event::{self, Event, KeyCode};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyModifiers};
 
 pub struct TuiInput {}
 
@@ -15,7 +15,29 @@ impl InputSource for TuiInput {
 
         match event {
             Event::Key(key) => match key.code {
+                KeyCode::Enter
+                    if key
+                        .modifiers
+                        .intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) =>
+                {
+                    Ok(InputEvent::Newline)
+                }
+                KeyCode::Up
+                    if key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    Ok(InputEvent::HistoryPrevious)
+                }
+                KeyCode::Down
+                    if key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    Ok(InputEvent::HistoryNext)
+                }
                 KeyCode::Enter => Ok(InputEvent::Submit),
+                KeyCode::Tab => Ok(InputEvent::Complete),
                 KeyCode::PageUp | KeyCode::Up => Ok(InputEvent::ScrollUp),
                 KeyCode::PageDown | KeyCode::Down => Ok(InputEvent::ScrollDown),
                 KeyCode::Esc => Ok(InputEvent::Close),
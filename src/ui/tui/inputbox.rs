@@ -1,6 +1,10 @@
+use ratatui::text::Line;
 use ratatui::widgets::{Block, Borders, Paragraph, Widget};
 use tui_input::Input;
 
+/// Maximum number of lines shown while composing a multi-line message
+const MAX_LINES: u16 = 6;
+
 pub struct InputBox<'a> {
     input: &'a Input,
 }
@@ -10,13 +14,40 @@ impl<'a> InputBox<'a> {
         Self { input }
     }
 
-    fn scroll(&self, width: u16) -> usize {
-        self.input.visual_scroll(width as usize - 2)
+    /// The height of the box fitting the lines of the input
+    pub fn height(&self) -> u16 {
+        let lines = self.input.value().split('\n').count() as u16;
+        lines.clamp(1, MAX_LINES) + 2
+    }
+
+    /// The line and the visual column of the cursor
+    fn cursor_position(&self) -> (u16, u16) {
+        let value = self.input.value();
+        let index = value
+            .char_indices()
+            .nth(self.input.cursor())
+            .map_or(value.len(), |(index, _)| index);
+        let before = &value[..index];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        (
+            before.matches('\n').count() as u16,
+            Line::raw(&before[line_start..]).width() as u16,
+        )
+    }
+
+    fn scroll(&self, width: u16, height: u16) -> (u16, u16) {
+        let (row, column) = self.cursor_position();
+        (
+            row.saturating_sub(height.saturating_sub(3)),
+            column.saturating_sub(width.saturating_sub(3)),
+        )
     }
 
-    pub fn offset_x(&self, width: u16) -> u16 {
-        let scroll = self.scroll(width);
-        (self.input.cursor().max(scroll) - scroll) as u16
+    /// The position of the cursor inside the box
+    pub fn offset(&self, width: u16, height: u16) -> (u16, u16) {
+        let (row, column) = self.cursor_position();
+        let (scroll_y, scroll_x) = self.scroll(width, height);
+        (column - scroll_x, row - scroll_y)
     }
 }
 
@@ -25,9 +56,9 @@ impl Widget for InputBox<'_> {
     where
         Self: Sized,
     {
-        let scroll = self.scroll(area.width);
+        let scroll = self.scroll(area.width, area.height);
         let input_widget = Paragraph::new(self.input.value())
-            .scroll((0, scroll as u16))
+            .scroll(scroll)
             .block(Block::default().borders(Borders::ALL).title("INPUT"));
         input_widget.render(area, buf);
     }
@@ -1,19 +1,29 @@
 mod chatbox;
+mod completion_popup;
 mod error_popup;
+mod initiative_panel;
 mod input;
 mod inputbox;
 mod renderer;
+mod stats_panel;
+mod status_panel;
 
 use crate::ui::UserInterface;
 use crate::ui::tui::{input::TuiInput, renderer::TuiRenderer};
 use anyhow::Result;
+use ratatui::crossterm::event::{
+    KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
 use ratatui::crossterm::execute;
 use ratatui::crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    supports_keyboard_enhancement,
 };
 use std::io;
 
-struct TuiBackendGuard;
+struct TuiBackendGuard {
+    keyboard_enhanced: bool,
+}
 
 impl TuiBackendGuard {
     fn new() -> io::Result<Self> {
@@ -22,12 +32,24 @@ impl TuiBackendGuard {
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen)?;
 
-        Ok(Self)
+        // lets terminals that support it report Shift+Enter apart from Enter
+        let keyboard_enhanced = supports_keyboard_enhancement().unwrap_or(false);
+        if keyboard_enhanced {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+            )?;
+        }
+
+        Ok(Self { keyboard_enhanced })
     }
 }
 
 impl Drop for TuiBackendGuard {
     fn drop(&mut self) {
+        if self.keyboard_enhanced {
+            _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
         _ = disable_raw_mode();
         _ = execute!(io::stdout(), LeaveAlternateScreen);
     }
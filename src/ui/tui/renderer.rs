@@ -1,9 +1,13 @@
 use crate::chat::AppState;
+use crate::ui::tui::completion_popup::CompletionPopup;
 use crate::ui::tui::error_popup::ErrorPopup;
+use crate::ui::tui::initiative_panel::InitiativePanel;
+use crate::ui::tui::stats_panel::StatsPanel;
+use crate::ui::tui::status_panel::StatusPanel;
 use crate::ui::tui::{TuiBackendGuard, chatbox::ChatBox, inputbox::InputBox};
 use crate::ui::{ChatRenderer, InputEvent};
 use anyhow::Result;
-use ratatui::layout::{Constraint, Layout};
+use ratatui::layout::{Constraint, Direction, Layout};
 use ratatui::{Terminal, prelude::CrosstermBackend};
 use std::io::{self, Stdout};
 
@@ -61,22 +65,65 @@ impl TuiRenderer {
 impl ChatRenderer for TuiRenderer {
     async fn draw(&mut self, state: &AppState) -> Result<()> {
         self.terminal.draw(|frame| {
+            let input_box = InputBox::new(state.input());
             let chunks = Layout::default()
-                .constraints([Constraint::Min(1), Constraint::Length(3)])
+                .constraints([Constraint::Min(1), Constraint::Length(input_box.height())])
                 .split(frame.area());
 
-            let chatbox = ChatBox::new(state.messages());
-
-            let input_box = InputBox::new(state.input());
-
-            frame.set_cursor_position((
-                chunks[1].x + 1 + input_box.offset_x(chunks[1].width),
-                chunks[1].y + 1,
-            ));
-
-            frame.render_stateful_widget(chatbox, chunks[0], &mut self.scroll_state);
+            let chatbox = ChatBox::new(state.messages(), state.show_timestamps());
+
+            let (offset_x, offset_y) = input_box.offset(chunks[1].width, chunks[1].height);
+            frame.set_cursor_position((chunks[1].x + 1 + offset_x, chunks[1].y + 1 + offset_y));
+
+            let initiative = state.initiative().map(InitiativePanel::new);
+            let status = state
+                .table_status()
+                .map(|(players, status)| StatusPanel::new(players, status));
+            let stats = state.stats().map(StatsPanel::new);
+
+            let chat_area = if initiative.is_some() || status.is_some() || stats.is_some() {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Min(1), Constraint::Length(30)])
+                    .split(chunks[0]);
+
+                // the turn order on top of the side column, the table status and the stats below
+                let mut side = columns[1];
+                if let Some(panel) = initiative {
+                    let rows = Layout::default()
+                        .constraints([Constraint::Length(panel.height()), Constraint::Min(0)])
+                        .split(side);
+                    frame.render_widget(panel, rows[0]);
+                    side = rows[1];
+                }
+                if let Some(panel) = status {
+                    let height = match stats {
+                        Some(_) => Constraint::Length(panel.height()),
+                        None => Constraint::Min(0),
+                    };
+                    let rows = Layout::default()
+                        .constraints([height, Constraint::Min(0)])
+                        .split(side);
+                    frame.render_widget(panel, rows[0]);
+                    side = rows[1];
+                }
+                if let Some(panel) = stats {
+                    frame.render_widget(panel, side);
+                }
+                columns[0]
+            } else {
+                chunks[0]
+            };
+
+            frame.render_stateful_widget(chatbox, chat_area, &mut self.scroll_state);
             frame.render_widget(input_box, chunks[1]);
 
+            if let Some(completion) = state.completion() {
+                let popup = CompletionPopup::new(completion);
+                let area = popup.area(chunks[1]);
+                frame.render_widget(popup, area);
+            }
+
             if let Some(error_message) = state.error_popup() {
                 frame.render_widget(ErrorPopup::new(error_message), frame.area());
             }
@@ -96,6 +143,10 @@ impl ChatRenderer for TuiRenderer {
                 true
             }
             InputEvent::Submit
+            | InputEvent::Newline
+            | InputEvent::Complete
+            | InputEvent::HistoryPrevious
+            | InputEvent::HistoryNext
             | InputEvent::Terminal(_)
             | InputEvent::Close
             | InputEvent::Redraw => false,
@@ -0,0 +1,109 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Widget, Wrap};
+
+use crate::chat::{PlayerStats, RollStats};
+
+/// Significance level below which the dice of a player are flagged as suspicious
+const SUSPICIOUS_P_VALUE: f64 = 0.01;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub struct StatsPanel<'a> {
+    stats: &'a RollStats,
+}
+
+impl<'a> StatsPanel<'a> {
+    pub fn new(stats: &'a RollStats) -> Self {
+        Self { stats }
+    }
+
+    fn player_lines(player: &PlayerStats) -> Vec<Line<'_>> {
+        let dim = Style::default().fg(Color::DarkGray);
+        let mut lines = vec![Line::from(vec![
+            Span::styled(
+                player.name.as_str(),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(format!("  {} rolls", player.rolls), dim),
+        ])];
+
+        if let Some(average) = player.d20_average() {
+            lines.push(Line::from(vec![
+                Span::raw(format!(" d20 avg {:.1} ", average)),
+                Span::styled("(10.5)", dim),
+            ]));
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!(" nat 20 ×{}", player.natural_20s()),
+                    Style::default().fg(Color::LightGreen),
+                ),
+                Span::styled(
+                    format!("  nat 1 ×{}", player.natural_1s()),
+                    Style::default().fg(Color::LightRed),
+                ),
+            ]));
+
+            // each face of the d20 relative to the most frequent one
+            let counts = player.d20();
+            let highest = counts.iter().copied().max().unwrap_or(0).max(1);
+            let histogram: String = counts
+                .iter()
+                .map(|&count| BARS[(count * (BARS.len() as u32 - 1)).div_ceil(highest) as usize])
+                .collect();
+            lines.push(Line::from(vec![
+                Span::raw(" "),
+                Span::styled(histogram, Style::default().fg(Color::Yellow)),
+            ]));
+        }
+
+        lines.push(match player.fairness() {
+            Some(fairness) => {
+                let style = if fairness.p_value < SUSPICIOUS_P_VALUE {
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::Green)
+                };
+                Line::from(Span::styled(
+                    format!(
+                        " χ² {:.1} (df {}) p={:.2}",
+                        fairness.chi_squared, fairness.degrees_of_freedom, fairness.p_value
+                    ),
+                    style,
+                ))
+            }
+            None => Line::from(Span::styled(" not enough rolls to test", dim)),
+        });
+        lines
+    }
+}
+
+impl Widget for StatsPanel<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let mut lines = Vec::new();
+        for player in self.stats.players() {
+            if !lines.is_empty() {
+                lines.push(Line::raw(""));
+            }
+            lines.extend(Self::player_lines(player));
+        }
+        if lines.is_empty() {
+            lines.push(Line::styled(
+                "No rolls yet",
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(" Dice Stats "))
+            .render(area, buf);
+    }
+}
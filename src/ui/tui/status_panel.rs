@@ -0,0 +1,94 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Widget, Wrap};
+
+use crate::status::{Combatant, TableStatus};
+
+/// The players at the table and the hit points and conditions of the combatants
+pub struct StatusPanel<'a> {
+    lines: Vec<Line<'a>>,
+}
+
+impl<'a> StatusPanel<'a> {
+    pub fn new(players: Vec<String>, status: &'a TableStatus) -> Self {
+        let mut lines: Vec<Line> = players
+            .into_iter()
+            .map(|player| Line::styled(player, Style::default().fg(Color::Cyan)))
+            .collect();
+        for combatant in status.combatants() {
+            if !lines.is_empty() {
+                lines.push(Line::raw(""));
+            }
+            lines.extend(Self::combatant_lines(combatant));
+        }
+        Self { lines }
+    }
+
+    /// The height fitting every line within the borders, when none of them wraps
+    pub fn height(&self) -> u16 {
+        self.lines.len() as u16 + 2
+    }
+
+    fn combatant_lines(combatant: &Combatant) -> Vec<Line<'_>> {
+        let dim = Style::default().fg(Color::DarkGray);
+        let mut header = vec![Span::styled(
+            combatant.name.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        if let Some(hp) = &combatant.hp {
+            let color = match hp.current * 4 / hp.max {
+                0 if hp.current == 0 => Color::DarkGray,
+                0 => Color::Red,
+                1 => Color::Yellow,
+                _ => Color::Green,
+            };
+            header.push(Span::styled(
+                format!("  {}/{}", hp.current, hp.max),
+                Style::default().fg(color),
+            ));
+            if hp.temp > 0 {
+                header.push(Span::styled(
+                    format!(" +{}", hp.temp),
+                    Style::default().fg(Color::LightBlue),
+                ));
+            }
+        }
+
+        let mut lines = vec![Line::from(header)];
+        if !combatant.conditions.is_empty() {
+            let conditions: Vec<String> = combatant
+                .conditions
+                .iter()
+                .map(|condition| match condition.rounds {
+                    Some(rounds) => format!("{} ({})", condition.name, rounds),
+                    None => condition.name.clone(),
+                })
+                .collect();
+            lines.push(Line::from(vec![
+                Span::raw(" "),
+                Span::styled(conditions.join(", "), Style::default().fg(Color::LightRed)),
+            ]));
+        }
+        if let Some(spell) = &combatant.concentration {
+            lines.push(Line::from(Span::styled(
+                format!(" ◎ {}", spell),
+                dim.add_modifier(Modifier::ITALIC),
+            )));
+        }
+        lines
+    }
+}
+
+impl Widget for StatusPanel<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        Paragraph::new(self.lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(" Table "))
+            .render(area, buf);
+    }
+}